@@ -1,7 +1,42 @@
-use jellyfin_rpc::{Button, DisplayFormat, MediaType};
-use log::debug;
-use serde::{Deserialize, Serialize};
+//! Config file handling.
+//!
+//! Every field in the config can be overridden without touching the file,
+//! values are applied in this order with later ones taking precedence:
+//!
+//! 1. Built-in defaults
+//! 2. The config file (JSON, TOML or YAML)
+//! 3. Environment variables: `JELLYFIN_RPC_` followed by the path to the field in uppercase,
+//!    with `__` between each part. `JELLYFIN_RPC_JELLYFIN__API_KEY` sets `jellyfin.api_key`.
+//! 4. `--set key=value` flags, with `.` between each part: `--set jellyfin.api_key=abc`.
+//!
+//! Override values are read as JSON when that gives a type the field takes,
+//! so `1234` is a number for a port but stays a string for a username or api key.
+//! Environment variables that don't match a field are ignored with a warning,
+//! `--set` with an unknown field is an error.
+//! The config file isn't needed when `jellyfin.url`, `jellyfin.api_key`
+//! and `jellyfin.username` are all overridden.
+//!
+//! Old config files are upgraded to the current shape when they're loaded,
+//! see [`crate::migrate`].
+//!
+//! A config can have named `profiles`, each holding any part of the config.
+//! The profile chosen with `--profile` is merged over the rest of the file before overrides
+//! are applied. A profile can also be its own file called `main-<profile>.json`
+//! (or `.toml`/`.yaml`) next to `main.json`, which is used instead of `main.json` when it exists.
+
+use crate::migrate;
+use crate::secrets::{self, Secret};
+#[cfg(feature = "transform")]
+use jellyfin_rpc::ImageTransform;
+use jellyfin_rpc::{
+    BlacklistAction, Button, ContentRule, DisplayFormat, MediaType, Schedule, ServerKind,
+};
+use log::{debug, warn};
+use schemars::JsonSchema;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
 use std::env;
+use std::path::Path;
 
 /// Main struct containing every other struct in the file.
 ///
@@ -17,12 +52,18 @@ pub struct Config {
     pub imgur: Imgur,
     /// Images configuration.
     pub images: Images,
+    /// Quiet hours and scheduled display formats.
+    pub schedule: Option<Schedule>,
+    /// Places other than discord where the activity is shown.
+    pub outputs: Option<Outputs>,
 }
 
 /// This struct contains every "required" part of the config.
 pub struct Jellyfin {
     /// URL to the jellyfin server.
     pub url: String,
+    /// URL to the jellyfin server that people viewing the activity can reach.
+    pub public_url: Option<String>,
     /// Api key from the jellyfin server, used to gather what's being watched.
     pub api_key: String,
     /// Username of the person that info should be gathered from.
@@ -35,6 +76,8 @@ pub struct Jellyfin {
     pub episodes: DisplayOptions,
     /// Blacklist configuration.
     pub blacklist: Blacklist,
+    /// Whitelist configuration.
+    pub whitelist: Whitelist,
     /// Self signed certificate option
     pub self_signed_cert: bool,
     /// Simple episode name
@@ -43,6 +86,38 @@ pub struct Jellyfin {
     pub append_prefix: bool,
     /// Add a divider between numbers
     pub add_divider: bool,
+    /// Priority of this server when other servers are playing something too.
+    pub priority: Option<i32>,
+    /// Whether the server runs jellyfin or Emby.
+    pub server_kind: Option<ServerKind>,
+    /// Other servers to watch at the same time.
+    pub servers: Vec<JellyfinServer>,
+}
+
+/// Another jellyfin server with its own credentials, users and blacklist.
+pub struct JellyfinServer {
+    /// Name used in logs.
+    pub name: Option<String>,
+    /// URL to the jellyfin server.
+    pub url: String,
+    /// URL to the jellyfin server that people viewing the activity can reach.
+    pub public_url: Option<String>,
+    /// Api key from the jellyfin server.
+    pub api_key: String,
+    /// Usernames whose activity should be shown.
+    pub username: Vec<String>,
+    /// Self signed certificate option
+    pub self_signed_cert: bool,
+    /// Which server is displayed when several are playing, highest wins.
+    pub priority: Option<i32>,
+    /// Whether the server runs jellyfin or Emby.
+    pub server_kind: Option<ServerKind>,
+    /// Only show `"jellyfin"` buttons for items this user of the server can access.
+    pub jellyfin_button_user: Option<String>,
+    /// Blacklist configuration.
+    pub blacklist: Blacklist,
+    /// Whitelist configuration.
+    pub whitelist: Whitelist,
 }
 
 /// Contains configuration for Music/Movie display.
@@ -51,6 +126,8 @@ pub struct DisplayOptions {
     pub display: Option<DisplayFormat>,
     /// Separator is what should be between the artist(s) and the `display` options.
     pub separator: Option<String>,
+    /// Buttons to use for this media type instead of `discord.buttons`.
+    pub buttons: Option<Vec<Button>>,
 }
 
 /// Discord configuration
@@ -59,6 +136,10 @@ pub struct Discord {
     pub application_id: Option<String>,
     /// Set custom buttons to be displayed.
     pub buttons: Option<Vec<Button>>,
+    /// Controls which providers `"dynamic"` buttons link to.
+    pub dynamic_buttons: Option<DynamicButtons>,
+    /// Only show `"jellyfin"` buttons for items this jellyfin user can access.
+    pub jellyfin_button_user: Option<String>,
     /// Show status when media is paused
     pub show_paused: bool,
 }
@@ -70,6 +151,9 @@ pub struct Images {
     pub enable_images: bool,
     /// Enables imgur images.
     pub imgur_images: bool,
+    /// Crop/pad/decorate images before they're uploaded.
+    #[cfg(feature = "transform")]
+    pub transform: Option<ImageTransform>,
 }
 
 impl Config {
@@ -78,31 +162,102 @@ impl Config {
     }
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, JsonSchema)]
 #[serde(rename_all = "lowercase")]
 pub struct ConfigBuilder {
+    /// Lets editors find the schema of the config, see `jellyfin-rpc schema`.
+    #[serde(rename = "$schema")]
+    pub schema: Option<String>,
+    /// Version of the config, old configs are upgraded when they're loaded.
+    pub version: Option<u64>,
+    /// Jellyfin server and what should be displayed.
     pub jellyfin: JellyfinBuilder,
+    /// Discord application and buttons.
     pub discord: Option<DiscordBuilder>,
-    pub imgur: Option<Imgur>,
+    /// Imgur account used to upload images.
+    pub imgur: Option<ImgurBuilder>,
+    /// Images configuration.
     pub images: Option<ImagesBuilder>,
+    /// Quiet hours and scheduled display formats.
+    pub schedule: Option<Schedule>,
+    /// Places other than discord where the activity is shown.
+    pub outputs: Option<Outputs>,
+    /// Named parts of the config that replace the ones above when selected with `--profile`.
+    pub profiles: Option<BTreeMap<String, serde_json::Value>>,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, JsonSchema)]
 pub struct JellyfinBuilder {
+    /// URL to the jellyfin server.
     pub url: String,
+    /// URL to the jellyfin server that people viewing the activity can reach.
+    pub public_url: Option<String>,
+    /// Api key from the jellyfin server, can be left out when `api_key_file` or the keyring is used.
+    #[serde(default)]
     pub api_key: String,
+    /// File containing the api key.
+    pub api_key_file: Option<String>,
+    /// Usernames whose activity should be shown.
     pub username: Username,
+    /// Music display options.
     pub music: Option<DisplayOptionsBuilder>,
+    /// Movie display options.
     pub movies: Option<DisplayOptionsBuilder>,
+    /// Episode display options.
     pub episodes: Option<DisplayOptionsBuilder>,
+    /// Content that shouldn't be shown.
     pub blacklist: Option<Blacklist>,
+    /// Only content that should be shown.
+    pub whitelist: Option<Whitelist>,
+    /// Accept self signed certificates from the server.
     pub self_signed_cert: Option<bool>,
+    /// Deprecated, use `episodes.display` instead.
+    #[schemars(extend("deprecated" = true))]
     pub show_simple: Option<bool>,
+    /// Deprecated, use `episodes.display` instead.
+    #[schemars(extend("deprecated" = true))]
     pub append_prefix: Option<bool>,
+    /// Deprecated, use `episodes.display` instead.
+    #[schemars(extend("deprecated" = true))]
     pub add_divider: Option<bool>,
+    /// Which server is displayed when several are playing, highest wins. Defaults to 0.
+    pub priority: Option<i32>,
+    /// Set to `"emby"` when the server runs Emby. Defaults to `"jellyfin"`.
+    pub server_kind: Option<ServerKind>,
+    /// Other servers to watch at the same time, with their own credentials, users and blacklist.
+    pub servers: Option<Vec<JellyfinServerBuilder>>,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, JsonSchema)]
+pub struct JellyfinServerBuilder {
+    /// Name used in logs, defaults to the host of the url.
+    pub name: Option<String>,
+    /// URL to the jellyfin server.
+    pub url: String,
+    /// URL to the jellyfin server that people viewing the activity can reach.
+    pub public_url: Option<String>,
+    /// Api key from the jellyfin server, can be left out when `api_key_file` is used.
+    #[serde(default)]
+    pub api_key: String,
+    /// File containing the api key.
+    pub api_key_file: Option<String>,
+    /// Usernames whose activity should be shown.
+    pub username: Username,
+    /// Accept self signed certificates from the server.
+    pub self_signed_cert: Option<bool>,
+    /// Which server is displayed when several are playing, highest wins. Defaults to 0.
+    pub priority: Option<i32>,
+    /// Set to `"emby"` when the server runs Emby. Defaults to `"jellyfin"`.
+    pub server_kind: Option<ServerKind>,
+    /// Only show `"jellyfin"` buttons for items this user of the server can access.
+    pub jellyfin_button_user: Option<String>,
+    /// Content from this server that shouldn't be shown.
+    pub blacklist: Option<Blacklist>,
+    /// Only content from this server that should be shown.
+    pub whitelist: Option<Whitelist>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, JsonSchema)]
 #[serde(untagged)]
 pub enum Username {
     /// If the username is a `Vec<String>`.
@@ -111,13 +266,17 @@ pub enum Username {
     String(String),
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, JsonSchema)]
 pub struct DisplayOptionsBuilder {
+    /// What should be displayed.
     pub display: Option<Display>,
+    /// What `{sep}` is replaced with.
     pub separator: Option<String>,
+    /// Buttons to use instead of `discord.buttons`.
+    pub buttons: Option<Vec<Button>>,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, JsonSchema)]
 #[serde(untagged)]
 pub enum Display {
     /// If the Display is a `Vec<String>`.
@@ -129,42 +288,129 @@ pub enum Display {
 }
 
 /// Blacklist MediaTypes and libraries.
-#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, JsonSchema)]
 pub struct Blacklist {
     /// `Vec<String>` of MediaTypes to blacklist
     pub media_types: Option<Vec<MediaType>>,
     /// `Vec<String>` of libraries to blacklist
     pub libraries: Option<Vec<String>>,
+    /// Rules matching genres, tags, ratings, studios or titles to blacklist
+    pub rules: Option<Vec<ContentRule>>,
+    /// Jellyfin tags that hide an item, or everything in a season/series/album that has them
+    pub private_tags: Option<Vec<String>>,
+    /// What to do with blacklisted content, defaults to hiding it
+    pub action: Option<BlacklistAction>,
+    /// Seconds the library list is kept before it's reloaded, defaults to 3600
+    pub cache_ttl: Option<u64>,
+    /// Hide content while the library list or its tags can't be loaded, defaults to false
+    pub fail_closed: Option<bool>,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+/// Only show MediaTypes and libraries that are in the whitelist.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, JsonSchema)]
+pub struct Whitelist {
+    /// `Vec<String>` of MediaTypes to allow
+    pub media_types: Option<Vec<MediaType>>,
+    /// `Vec<String>` of libraries to allow
+    pub libraries: Option<Vec<String>>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, JsonSchema)]
 pub struct DiscordBuilder {
+    /// Custom Discord application id.
     pub application_id: Option<String>,
+    /// Buttons to display, at most 2.
     pub buttons: Option<Vec<Button>>,
+    /// Controls which providers `"dynamic"` buttons link to.
+    pub dynamic_buttons: Option<DynamicButtons>,
+    /// Only show `"jellyfin"` buttons for items this jellyfin user can access.
+    pub jellyfin_button_user: Option<String>,
+    /// Show status when media is paused, defaults to true.
     pub show_paused: Option<bool>,
 }
 
+/// Provider preferences for `"dynamic"` buttons.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, JsonSchema)]
+pub struct DynamicButtons {
+    /// Provider names in order of preference, e.g. `["IMDb", "TheMovieDb", "MusicBrainz"]`
+    pub providers: Option<Vec<String>>,
+    /// Custom button label per provider name.
+    pub labels: Option<HashMap<String, String>>,
+}
+
+/// Places other than discord where the activity is shown,
+/// each of them gets the activity as a JSON object and `{}` when it's cleared.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, JsonSchema)]
+pub struct Outputs {
+    /// Show the activity on discord, defaults to true.
+    pub discord: Option<bool>,
+    /// Print the activity to stdout as a line of JSON whenever it changes.
+    pub stdout: Option<bool>,
+    /// File or named pipe the activity is written to, for status bars like waybar or polybar.
+    pub file: Option<String>,
+    /// Url the activity is POSTed to whenever it changes.
+    pub webhook: Option<String>,
+    /// MQTT broker the activity is published to whenever it changes, for Home Assistant and the like.
+    /// Only works when jellyfin-rpc is built with the `mqtt` feature.
+    pub mqtt: Option<Mqtt>,
+}
+
+/// Connection to an MQTT broker.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, JsonSchema)]
+pub struct Mqtt {
+    /// Hostname of the broker.
+    pub host: String,
+    /// Port of the broker, defaults to 1883.
+    pub port: Option<u16>,
+    /// Topic the activity is published to, defaults to `"jellyfin-rpc/activity"`.
+    pub topic: Option<String>,
+    /// Username to log in to the broker with.
+    pub username: Option<String>,
+    /// Password to log in to the broker with, can be left out when `password_file` or the keyring is used.
+    pub password: Option<String>,
+    /// File containing the password.
+    pub password_file: Option<String>,
+}
+
 /// Imgur configuration
-#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
 pub struct Imgur {
     /// Contains the client ID used to upload images to imgur.
     pub client_id: Option<String>,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, JsonSchema)]
+pub struct ImgurBuilder {
+    /// Client id used to upload images to imgur.
+    pub client_id: Option<String>,
+    /// File containing the client id.
+    pub client_id_file: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, JsonSchema)]
 pub struct ImagesBuilder {
+    /// Show images, defaults to false.
     pub enable_images: Option<bool>,
+    /// Upload images to imgur instead of linking to the jellyfin server, defaults to false.
     pub imgur_images: Option<bool>,
+    /// Crop/pad/decorate images before they're uploaded.
+    #[cfg(feature = "transform")]
+    pub transform: Option<ImageTransform>,
 }
 
 /// Find urls.json in filesystem, used to store images that were already previously uploaded to imgur.
 ///
 /// This is to avoid the user having to specify a filepath on launch.
+/// Each profile gets its own `urls-<profile>.json` since they can use different servers.
 ///
 /// Default urls.json path depends on OS
 /// Windows: `%appdata%\jellyfin-rpc\urls.json`
 /// Linux/macOS: `~/.config/jellyfin-rpc/urls.json`
-pub fn get_urls_path() -> Result<String, Box<dyn std::error::Error>> {
+pub fn get_urls_path(profile: Option<&str>) -> Result<String, Box<dyn std::error::Error>> {
+    let file = match profile {
+        Some(profile) => format!("urls-{}.json", profile_name(profile)?),
+        None => "urls.json".to_string(),
+    };
+
     if cfg!(not(windows)) {
         debug!("Platform is not Windows");
         let xdg_config_home = match env::var("XDG_CONFIG_HOME") {
@@ -172,80 +418,586 @@ pub fn get_urls_path() -> Result<String, Box<dyn std::error::Error>> {
             Err(_) => env::var("HOME")? + "/.config",
         };
 
-        Ok(xdg_config_home + ("/jellyfin-rpc/urls.json"))
+        Ok(xdg_config_home + "/jellyfin-rpc/" + &file)
     } else {
         debug!("Platform is Windows");
         let app_data = env::var("APPDATA")?;
-        Ok(app_data + r"\jellyfin-rpc\urls.json")
+        Ok(app_data + r"\jellyfin-rpc\" + &file)
     }
 }
 
-/// Find default config path (main.json) in filesystem.
+/// Find default config path (main.json, main.toml, main.yaml or main.yml) in filesystem.
 ///
 /// This is to avoid the user having to specify a filepath on launch.
+/// If none of them exist the path to main.json is returned.
+/// With a profile, `main-<profile>.json` and the others are used first if they exist
+/// and `main-<profile>.json` is returned if nothing exists.
 ///
 /// Default config path depends on OS
 /// Windows: `%appdata%\jellyfin-rpc\main.json`
 /// Linux/macOS: `~/.config/jellyfin-rpc/main.json`
-pub fn get_config_path() -> Result<String, Box<dyn std::error::Error>> {
+pub fn get_config_path(profile: Option<&str>) -> Result<String, Box<dyn std::error::Error>> {
     debug!("Getting config path");
-    if cfg!(not(windows)) {
+    let config_dir = if cfg!(not(windows)) {
         debug!("Platform is not Windows");
         let xdg_config_home = match env::var("XDG_CONFIG_HOME") {
             Ok(xdg_config_home) => xdg_config_home,
             Err(_) => env::var("HOME")? + "/.config",
         };
 
-        Ok(xdg_config_home + "/jellyfin-rpc/main.json")
+        xdg_config_home + "/jellyfin-rpc/"
     } else {
         debug!("Platform is Windows");
-        let app_data = env::var("APPDATA")?;
-        Ok(app_data + r"\jellyfin-rpc\main.json")
+        env::var("APPDATA")? + r"\jellyfin-rpc\"
+    };
+
+    find_config(&config_dir, profile)
+}
+
+/// The config file for `profile` in `config_dir`, see [get_config_path].
+pub(crate) fn find_config(
+    config_dir: &str,
+    profile: Option<&str>,
+) -> Result<String, Box<dyn std::error::Error>> {
+    let profile = profile
+        .map(|profile| profile_name(profile).map(|profile| format!("main-{}", profile)))
+        .transpose()?;
+    let default = profile.clone().unwrap_or("main".to_string()) + ".json";
+
+    let path = profile
+        .into_iter()
+        .chain(["main".to_string()])
+        .flat_map(|name| ["json", "toml", "yaml", "yml"].map(|ext| format!("{}.{}", name, ext)))
+        .map(|file| config_dir.to_string() + &file)
+        .find(|path| Path::new(path).exists())
+        .unwrap_or(config_dir.to_string() + &default);
+
+    Ok(path)
+}
+
+/// Checks that a profile name can be used in a file name, it can't contain path separators.
+pub fn profile_name(profile: &str) -> Result<String, String> {
+    if profile.is_empty() || profile == "." || profile == ".." {
+        return Err(format!("\"{}\" isn't a valid profile name", profile));
+    }
+
+    if profile.contains(['/', '\\']) {
+        return Err(format!(
+            "profile names can't contain / or \\, got \"{}\"",
+            profile
+        ));
+    }
+
+    Ok(profile.to_string())
+}
+
+/// File formats the config can be written in.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ConfigFormat {
+    Json,
+    Toml,
+    Yaml,
+}
+
+impl ConfigFormat {
+    /// Detects the format from the extension of the file, defaults to JSON.
+    pub fn from_path(path: &str) -> Self {
+        let extension = Path::new(path)
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .unwrap_or_default()
+            .to_lowercase();
+
+        match extension.as_str() {
+            "toml" => Self::Toml,
+            "yaml" | "yml" => Self::Yaml,
+            _ => Self::Json,
+        }
+    }
+
+    pub fn parse<T: DeserializeOwned>(&self, data: &str) -> Result<T, Box<dyn std::error::Error>> {
+        Ok(match self {
+            Self::Json => serde_json::from_str(data)?,
+            Self::Toml => toml::from_str(data)?,
+            Self::Yaml => serde_yaml::from_str(data)?,
+        })
+    }
+
+    pub fn serialize<T: Serialize>(&self, value: &T) -> Result<String, Box<dyn std::error::Error>> {
+        Ok(match self {
+            Self::Json => serde_json::to_string_pretty(value)?,
+            Self::Toml => toml::to_string_pretty(value)?,
+            Self::Yaml => serde_yaml::to_string(value)?,
+        })
+    }
+}
+
+/// Converts a config file to the format of `output`, detected by its extension.
+///
+/// The file is converted as-is without being validated,
+/// `null` values are left out since TOML doesn't have them.
+pub fn convert(input: &str, output: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let data = std::fs::read_to_string(input)?;
+    let mut config: serde_json::Value = ConfigFormat::from_path(input).parse(&data)?;
+
+    remove_nulls(&mut config);
+
+    std::fs::write(output, ConfigFormat::from_path(output).serialize(&config)?)?;
+
+    Ok(())
+}
+
+/// JSON Schema of the config file, used by editors for autocompletion and validation.
+pub fn schema() -> Result<String, Box<dyn std::error::Error>> {
+    let mut schema = schemars::schema_for!(ConfigBuilder);
+    schema.insert("title".to_string(), "Jellyfin-RPC config".into());
+
+    Ok(serde_json::to_string_pretty(&schema)?)
+}
+
+/// Merges the chosen profile over the rest of the config and removes `profiles`.
+///
+/// The profile has to exist unless `path` is the profile's own file.
+pub fn apply_profile(
+    config: &mut serde_json::Value,
+    path: &str,
+    profile: Option<&str>,
+) -> Result<(), String> {
+    let mut profiles = match config
+        .as_object_mut()
+        .and_then(|map| map.remove("profiles"))
+    {
+        Some(serde_json::Value::Object(profiles)) => profiles,
+        _ => serde_json::Map::new(),
+    };
+
+    let Some(name) = profile else {
+        return Ok(());
+    };
+
+    match profiles.remove(name) {
+        Some(profile) => {
+            debug!("Using profile {}", name);
+            merge(config, profile);
+            Ok(())
+        }
+        None if Path::new(path)
+            .file_stem()
+            .is_some_and(|stem| stem.to_string_lossy() == format!("main-{}", name)) =>
+        {
+            Ok(())
+        }
+        None => Err(format!(
+            "profile {} isn't in the config, available profiles: {}",
+            name,
+            profiles.keys().cloned().collect::<Vec<String>>().join(", ")
+        )),
+    }
+}
+
+/// Recursively merges `overlay` into `base`, anything that isn't an object is replaced.
+pub fn merge(base: &mut serde_json::Value, overlay: serde_json::Value) {
+    match (base, overlay) {
+        (serde_json::Value::Object(base), serde_json::Value::Object(overlay)) => {
+            for (key, value) in overlay {
+                match base.get_mut(&key) {
+                    Some(existing) => merge(existing, value),
+                    None => {
+                        base.insert(key, value);
+                    }
+                }
+            }
+        }
+        (base, overlay) => *base = overlay,
+    }
+}
+
+/// Path of the overridden field and the value it's set to.
+pub type Override = (Vec<String>, String);
+
+/// The environment variables followed by the `key=value` overrides, in the order they're applied.
+///
+/// Unlike the environment variables, an override for a field that doesn't exist is an error.
+pub fn collect_overrides(
+    overrides: &[String],
+    vars: impl Iterator<Item = (String, String)>,
+    schema: &serde_json::Value,
+) -> Result<Vec<Override>, Box<dyn std::error::Error>> {
+    let mut sets = Vec::new();
+    for set in overrides {
+        let (key, value) = parse_set(set)?;
+        if schema_types(schema, &key).is_none() {
+            return Err(format!("the config has no field {}", key.join(".")).into());
+        }
+        sets.push((key, value));
+    }
+
+    Ok(env_overrides(vars, schema)
+        .into_iter()
+        .chain(sets)
+        .collect())
+}
+
+/// Sets every override in `config`, typed by the field it overrides.
+pub fn apply_overrides(
+    config: &mut serde_json::Value,
+    overrides: &[Override],
+    schema: &serde_json::Value,
+) {
+    for (key, value) in overrides {
+        debug!("Overriding {}", key.join("."));
+        let types = schema_types(schema, key).unwrap_or_default();
+        let value = override_value(value, &types, get_value(config, key));
+        set_value(config, key, value);
+    }
+}
+
+/// Config overrides from `JELLYFIN_RPC_*` environment variables,
+/// variables that don't match a field of the config are skipped with a warning.
+pub fn env_overrides(
+    vars: impl Iterator<Item = (String, String)>,
+    schema: &serde_json::Value,
+) -> Vec<Override> {
+    let mut overrides: Vec<Override> = vars
+        .filter_map(|(name, value)| {
+            let key: Vec<String> = name
+                .strip_prefix("JELLYFIN_RPC_")?
+                .split("__")
+                .map(|part| part.to_lowercase())
+                .collect();
+
+            if schema_types(schema, &key).is_none() {
+                warn!(
+                    "Ignoring {}, the config has no field {}",
+                    name,
+                    key.join(".")
+                );
+                return None;
+            }
+
+            Some((key, value))
+        })
+        .collect();
+
+    // Shorter keys first so `JELLYFIN_RPC_JELLYFIN` doesn't replace more specific ones
+    overrides.sort_by_key(|(key, _)| key.len());
+
+    overrides
+}
+
+/// Splits `jellyfin.api_key=value` into the path to the field and the value.
+pub fn parse_set(set: &str) -> Result<Override, String> {
+    let (key, value) = set
+        .split_once('=')
+        .ok_or_else(|| format!("expected key=value, got: {}", set))?;
+
+    Ok((
+        key.trim().split('.').map(|part| part.to_string()).collect(),
+        value.to_string(),
+    ))
+}
+
+/// Reads an override as JSON if that gives one of the `types` the field takes,
+/// otherwise as a string. So `1234` stays a string for an api key but is a number for a port.
+///
+/// `existing` is the current value of the field, fields that take anything
+/// keep being a string if they were one.
+pub fn override_value(
+    value: &str,
+    types: &[String],
+    existing: Option<&serde_json::Value>,
+) -> serde_json::Value {
+    let string = serde_json::Value::String(value.to_string());
+
+    let Ok(json) = serde_json::from_str::<serde_json::Value>(value.trim()) else {
+        return string;
+    };
+
+    let json_type = match &json {
+        serde_json::Value::Null => "null",
+        serde_json::Value::Bool(_) => "boolean",
+        serde_json::Value::Number(number) if number.is_f64() => "number",
+        serde_json::Value::Number(_) => "integer",
+        serde_json::Value::String(_) => "string",
+        serde_json::Value::Array(_) => "array",
+        serde_json::Value::Object(_) => "object",
+    };
+
+    let allowed = types.iter().any(|allowed| match allowed.as_str() {
+        ANY_TYPE => !existing.is_some_and(serde_json::Value::is_string) || json.is_string(),
+        "number" => json_type == "number" || json_type == "integer",
+        allowed => allowed == json_type,
+    });
+
+    if allowed {
+        json
+    } else {
+        string
+    }
+}
+
+/// Stands for fields that take anything in [schema_types].
+const ANY_TYPE: &str = "any";
+
+/// JSON types the schema of the config allows for the field at `key`, like `["string", "null"]`.
+///
+/// `None` if the config has no such field.
+pub fn schema_types(schema: &serde_json::Value, key: &[String]) -> Option<Vec<String>> {
+    // A profile holds a part of the config
+    let key = match key {
+        [profiles, _, rest @ ..] if profiles == "profiles" && !rest.is_empty() => rest,
+        key => key,
+    };
+
+    let mut nodes = vec![schema];
+
+    for part in key {
+        nodes = nodes
+            .into_iter()
+            .flat_map(|node| resolve_schema(schema, node))
+            .filter_map(|node| {
+                if let Some(property) = node.pointer(&format!("/properties/{}", part)) {
+                    return Some(property);
+                }
+
+                if part.parse::<usize>().is_ok() {
+                    if let Some(items) = node.get("items") {
+                        return Some(items);
+                    }
+                }
+
+                node.get("additionalProperties")
+                    .filter(|additional| *additional != &serde_json::Value::Bool(false))
+            })
+            .collect();
+
+        if nodes.is_empty() {
+            return None;
+        }
+    }
+
+    let mut types = Vec::new();
+
+    for node in nodes
+        .into_iter()
+        .flat_map(|node| resolve_schema(schema, node))
+    {
+        match node.get("type") {
+            Some(serde_json::Value::String(name)) => types.push(name.clone()),
+            Some(serde_json::Value::Array(names)) => types.extend(
+                names
+                    .iter()
+                    .filter_map(|name| name.as_str().map(str::to_string)),
+            ),
+            // `true` and schemas without a type take anything
+            _ if node.get("enum").is_none() => types.push(ANY_TYPE.to_string()),
+            _ => (),
+        }
+
+        for value in node
+            .get("enum")
+            .and_then(|values| values.as_array())
+            .into_iter()
+            .flatten()
+        {
+            if value.is_string() {
+                types.push("string".to_string());
+            }
+        }
+    }
+
+    Some(types)
+}
+
+/// Follows `$ref`s and splits `anyOf`/`oneOf`/`allOf` into the schemas they consist of.
+fn resolve_schema<'a>(
+    root: &'a serde_json::Value,
+    node: &'a serde_json::Value,
+) -> Vec<&'a serde_json::Value> {
+    if let Some(path) = node.get("$ref").and_then(|path| path.as_str()) {
+        return match path.strip_prefix('#').and_then(|path| root.pointer(path)) {
+            Some(target) => resolve_schema(root, target),
+            None => Vec::new(),
+        };
+    }
+
+    let parts: Vec<&serde_json::Value> = ["anyOf", "oneOf", "allOf"]
+        .into_iter()
+        .filter_map(|key| node.get(key).and_then(|parts| parts.as_array()))
+        .flatten()
+        .collect();
+
+    if parts.is_empty() {
+        vec![node]
+    } else {
+        parts
+            .into_iter()
+            .flat_map(|part| resolve_schema(root, part))
+            .collect()
+    }
+}
+
+/// Current value of the field at `key`.
+fn get_value<'a>(config: &'a serde_json::Value, key: &[String]) -> Option<&'a serde_json::Value> {
+    key.iter().try_fold(config, |current, part| match current {
+        serde_json::Value::Array(values) => values.get(part.parse::<usize>().ok()?),
+        current => current.get(part),
+    })
+}
+
+/// Sets the field at `key` to `value`, creating any objects on the way.
+/// Numbers index into arrays that are already there, like `jellyfin.servers.0.url`.
+pub fn set_value(config: &mut serde_json::Value, key: &[String], value: serde_json::Value) {
+    let Some((last, parents)) = key.split_last() else {
+        return;
+    };
+
+    let mut current = config;
+
+    for part in parents {
+        if let Some(index) = array_index(current, part) {
+            current = &mut current[index];
+            continue;
+        }
+
+        if !current.is_object() {
+            *current = serde_json::Value::Object(serde_json::Map::new());
+        }
+
+        current = current
+            .as_object_mut()
+            .expect("Unreachable error")
+            .entry(part.as_str())
+            .or_insert(serde_json::Value::Null);
+    }
+
+    if let Some(index) = array_index(current, last) {
+        current[index] = value;
+        return;
+    }
+
+    if !current.is_object() {
+        *current = serde_json::Value::Object(serde_json::Map::new());
+    }
+
+    current
+        .as_object_mut()
+        .expect("Unreachable error")
+        .insert(last.to_string(), value);
+}
+
+/// `part` as an index into `value`, if `value` is an array long enough for it.
+fn array_index(value: &serde_json::Value, part: &str) -> Option<usize> {
+    let index = part.parse().ok()?;
+    (index < value.as_array()?.len()).then_some(index)
+}
+
+pub fn remove_nulls(value: &mut serde_json::Value) {
+    match value {
+        serde_json::Value::Object(map) => {
+            map.retain(|_, value| !value.is_null());
+            map.values_mut().for_each(remove_nulls);
+        }
+        serde_json::Value::Array(values) => {
+            values.retain(|value| !value.is_null());
+            values.iter_mut().for_each(remove_nulls);
+        }
+        _ => (),
     }
 }
 
 impl ConfigBuilder {
     fn new() -> Self {
         Self {
+            schema: None,
+            version: None,
             jellyfin: JellyfinBuilder {
                 url: "".to_string(),
+                public_url: None,
                 username: Username::String("".to_string()),
                 api_key: "".to_string(),
+                api_key_file: None,
                 music: None,
                 movies: None,
                 episodes: None,
                 blacklist: None,
+                whitelist: None,
                 self_signed_cert: None,
                 show_simple: Some(false),
                 append_prefix: Some(false),
                 add_divider: Some(false),
+                priority: None,
+                server_kind: None,
+                servers: None,
             },
             discord: None,
             imgur: None,
             images: None,
+            schedule: None,
+            outputs: None,
+            profiles: None,
         }
     }
 
-    /// Loads the config from the given path.
-    pub fn load(self, path: &str) -> Result<Self, Box<dyn std::error::Error>> {
+    /// Loads the config from the given path, the format is detected from the extension.
+    ///
+    /// The `profile` is merged over the file, then environment variables and `overrides`
+    /// (`key=value`) are applied on top. The file can be missing if there are any overrides.
+    pub fn load(
+        self,
+        path: &str,
+        profile: Option<&str>,
+        overrides: &[String],
+    ) -> Result<Self, Box<dyn std::error::Error>> {
+        self.load_with(path, profile, overrides, env::vars())
+    }
+
+    /// Same as [ConfigBuilder::load] with `vars` instead of the environment variables.
+    pub fn load_with(
+        self,
+        path: &str,
+        profile: Option<&str>,
+        overrides: &[String],
+        vars: impl Iterator<Item = (String, String)>,
+    ) -> Result<Self, Box<dyn std::error::Error>> {
         debug!("Config path is: {}", path);
 
-        let data = std::fs::read_to_string(path)?;
-        let config = serde_json::from_str(&data)?;
+        let schema = serde_json::to_value(schemars::schema_for!(ConfigBuilder))?;
+        let overrides = collect_overrides(overrides, vars, &schema)?;
+
+        let mut config = if !overrides.is_empty() && !Path::new(path).exists() {
+            debug!("No config file found, only using overrides");
+            serde_json::Value::Object(serde_json::Map::new())
+        } else {
+            let data = std::fs::read_to_string(path)?;
+            ConfigFormat::from_path(path).parse(&data)?
+        };
+
+        let version = migrate::migrate(&mut config)?;
+        if version < migrate::CURRENT_VERSION {
+            debug!(
+                "Upgraded config from version {}, run migrate-config to update the file",
+                version
+            );
+        }
+
+        apply_profile(&mut config, path, profile)?;
+        apply_overrides(&mut config, &overrides, &schema);
+
+        let config = serde_json::from_value(config)?;
 
         debug!("Config loaded successfully");
 
         Ok(config)
     }
 
-    pub fn build(self) -> Config {
-        let username = match self.jellyfin.username {
-            Username::Vec(usernames) => usernames,
-            Username::String(username) => username.split(',').map(|u| u.to_string()).collect(),
-        };
+    /// Builds the config, reading secrets from files or the secret store when they aren't in the config.
+    pub fn build(self) -> Result<Config, Box<dyn std::error::Error>> {
+        let username = self.jellyfin.username.into_vec();
 
         let music_display;
         let music_separator;
+        let music_buttons;
 
         if let Some(music) = self.jellyfin.music {
             if let Some(disp) = music.display {
@@ -259,13 +1011,16 @@ impl ConfigBuilder {
             }
 
             music_separator = music.separator;
+            music_buttons = music.buttons;
         } else {
             music_display = None;
             music_separator = None;
+            music_buttons = None;
         }
 
         let movie_display;
         let movie_separator;
+        let movie_buttons;
 
         if let Some(movies) = self.jellyfin.movies {
             if let Some(disp) = movies.display {
@@ -279,13 +1034,16 @@ impl ConfigBuilder {
             }
 
             movie_separator = movies.separator;
+            movie_buttons = movies.buttons;
         } else {
             movie_display = None;
             movie_separator = None;
+            movie_buttons = None;
         }
 
         let episode_display;
         let episode_separator;
+        let episode_buttons;
 
         if let Some(episodes) = self.jellyfin.episodes {
             if let Some(disp) = episodes.display {
@@ -299,44 +1057,102 @@ impl ConfigBuilder {
             }
 
             episode_separator = episodes.separator;
+            episode_buttons = episodes.buttons;
         } else {
             episode_display = None;
             episode_separator = None;
+            episode_buttons = None;
         }
 
         let media_types;
         let libraries;
+        let rules;
+        let private_tags;
+        let action;
+        let cache_ttl;
+        let fail_closed;
 
         if let Some(blacklist) = self.jellyfin.blacklist {
             media_types = blacklist.media_types;
             libraries = blacklist.libraries;
+            rules = blacklist.rules;
+            private_tags = blacklist.private_tags;
+            action = blacklist.action;
+            cache_ttl = blacklist.cache_ttl;
+            fail_closed = blacklist.fail_closed;
         } else {
             media_types = None;
             libraries = None;
+            rules = None;
+            private_tags = None;
+            action = None;
+            cache_ttl = None;
+            fail_closed = None;
         }
 
+        let whitelist = self.jellyfin.whitelist.unwrap_or(Whitelist {
+            media_types: None,
+            libraries: None,
+        });
+
         let application_id;
         let buttons;
+        let dynamic_buttons;
+        let jellyfin_button_user;
         let show_paused;
 
         if let Some(discord) = self.discord {
             application_id = discord.application_id;
             buttons = discord.buttons;
+            dynamic_buttons = discord.dynamic_buttons;
+            jellyfin_button_user = discord.jellyfin_button_user;
             show_paused = discord.show_paused.unwrap_or(true)
         } else {
             application_id = None;
             buttons = None;
+            dynamic_buttons = None;
+            jellyfin_button_user = None;
             show_paused = true;
         }
 
+        let store = secrets::default_store();
+
+        let api_key = secrets::resolve(
+            Secret::ApiKey,
+            Some(self.jellyfin.api_key),
+            self.jellyfin.api_key_file.as_deref(),
+            store.as_deref(),
+        )?
+        .unwrap_or_default();
+
         let client_id;
 
         if let Some(imgur) = self.imgur {
-            client_id = imgur.client_id;
+            client_id = secrets::resolve(
+                Secret::ImgurClientId,
+                imgur.client_id,
+                imgur.client_id_file.as_deref(),
+                store.as_deref(),
+            )?;
         } else {
             client_id = None
         }
 
+        let mut outputs = self.outputs;
+
+        if let Some(mqtt) = outputs.as_mut().and_then(|outputs| outputs.mqtt.as_mut()) {
+            if mqtt.username.is_some() {
+                mqtt.password = secrets::resolve(
+                    Secret::MqttPassword,
+                    mqtt.password.take(),
+                    mqtt.password_file.as_deref(),
+                    store.as_deref(),
+                )?;
+            }
+        }
+
+        #[cfg(feature = "transform")]
+        let transform = self.images.as_ref().and_then(|i| i.transform.clone());
         let enable_images;
         let imgur_images;
 
@@ -348,50 +1164,129 @@ impl ConfigBuilder {
             imgur_images = false;
         }
 
-        let url;
-
-        if self.jellyfin.url.ends_with("/") {
-            url = self.jellyfin.url;
+        let url = if self.jellyfin.url.ends_with("/") {
+            self.jellyfin.url
         } else {
-             url = self.jellyfin.url + "/"
-        }
+            self.jellyfin.url + "/"
+        };
+
+        let servers = self
+            .jellyfin
+            .servers
+            .unwrap_or_default()
+            .into_iter()
+            .map(JellyfinServerBuilder::build)
+            .collect::<Result<Vec<JellyfinServer>, _>>()?;
 
-        Config {
+        Ok(Config {
             jellyfin: Jellyfin {
                 url,
-                api_key: self.jellyfin.api_key,
+                public_url: self.jellyfin.public_url,
+                api_key,
                 username,
                 music: DisplayOptions {
                     display: music_display,
                     separator: music_separator,
+                    buttons: music_buttons,
                 },
                 movies: DisplayOptions {
                     display: movie_display,
                     separator: movie_separator,
+                    buttons: movie_buttons,
                 },
                 episodes: DisplayOptions {
                     display: episode_display,
                     separator: episode_separator,
+                    buttons: episode_buttons,
                 },
                 blacklist: Blacklist {
                     media_types,
                     libraries,
+                    rules,
+                    private_tags,
+                    action,
+                    cache_ttl,
+                    fail_closed,
                 },
+                whitelist,
                 self_signed_cert: self.jellyfin.self_signed_cert.unwrap_or(false),
                 show_simple: self.jellyfin.show_simple.unwrap_or(false),
                 append_prefix: self.jellyfin.append_prefix.unwrap_or(false),
                 add_divider: self.jellyfin.add_divider.unwrap_or(false),
+                priority: self.jellyfin.priority,
+                server_kind: self.jellyfin.server_kind,
+                servers,
             },
             discord: Discord {
                 application_id,
                 buttons,
+                dynamic_buttons,
+                jellyfin_button_user,
                 show_paused,
             },
             imgur: Imgur { client_id },
             images: Images {
                 enable_images,
                 imgur_images,
+                #[cfg(feature = "transform")]
+                transform,
             },
+            schedule: self.schedule,
+            outputs,
+        })
+    }
+}
+
+impl Username {
+    /// Every username, a single string can hold several separated by commas.
+    pub fn into_vec(self) -> Vec<String> {
+        match self {
+            Username::Vec(usernames) => usernames,
+            Username::String(username) => username.split(',').map(|u| u.to_string()).collect(),
         }
     }
 }
+
+impl JellyfinServerBuilder {
+    fn build(self) -> Result<JellyfinServer, Box<dyn std::error::Error>> {
+        // The keyring only holds the api key of the main server
+        let api_key = secrets::resolve(
+            Secret::ApiKey,
+            Some(self.api_key),
+            self.api_key_file.as_deref(),
+            None,
+        )?
+        .unwrap_or_default();
+
+        let url = if self.url.ends_with('/') {
+            self.url
+        } else {
+            self.url + "/"
+        };
+
+        Ok(JellyfinServer {
+            name: self.name,
+            url,
+            public_url: self.public_url,
+            api_key,
+            username: self.username.into_vec(),
+            self_signed_cert: self.self_signed_cert.unwrap_or(false),
+            priority: self.priority,
+            server_kind: self.server_kind,
+            jellyfin_button_user: self.jellyfin_button_user,
+            blacklist: self.blacklist.unwrap_or(Blacklist {
+                media_types: None,
+                libraries: None,
+                rules: None,
+                private_tags: None,
+                action: None,
+                cache_ttl: None,
+                fail_closed: None,
+            }),
+            whitelist: self.whitelist.unwrap_or(Whitelist {
+                media_types: None,
+                libraries: None,
+            }),
+        })
+    }
+}
@@ -0,0 +1,432 @@
+use clap::Args;
+use colored::Colorize;
+use log::{debug, info, warn};
+use reqwest::blocking::Client;
+use reqwest::header::{HeaderMap, HeaderValue, AUTHORIZATION};
+use serde::Deserialize;
+use std::cell::RefCell;
+use std::io::{BufRead, IsTerminal, Write};
+use std::path::Path;
+
+use crate::config::ConfigFormat;
+use crate::VERSION;
+
+/// Options for `jellyfin-rpc init`, anything that isn't given is asked for
+/// unless `--non-interactive` is used.
+#[derive(Args, Debug)]
+pub struct InitArgs {
+    #[arg(long, help = "URL to the jellyfin server")]
+    url: Option<String>,
+    #[arg(long, help = "Api key to use instead of logging in")]
+    api_key: Option<String>,
+    #[arg(
+        long,
+        help = "User to log in as, an api key is created if it's an administrator"
+    )]
+    login_user: Option<String>,
+    #[arg(
+        long,
+        help = "Read the password of the user to log in as from the first line of stdin"
+    )]
+    password_stdin: bool,
+    #[arg(
+        long = "username",
+        help = "User whose activity should be shown, can be used multiple times"
+    )]
+    usernames: Vec<String>,
+    #[arg(
+        long = "blacklist-library",
+        help = "Library that shouldn't be shown, can be used multiple times"
+    )]
+    blacklist_libraries: Vec<String>,
+    #[arg(long, help = "Accept self-signed certificates")]
+    self_signed: bool,
+    #[arg(short = 'f', long, help = "Overwrite the config if it exists")]
+    force: bool,
+    #[arg(long, help = "Fail instead of asking when something is missing")]
+    non_interactive: bool,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct PublicSystemInfo {
+    server_name: Option<String>,
+    version: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct AuthenticationResult {
+    access_token: String,
+    user: User,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct User {
+    name: String,
+    policy: Option<UserPolicy>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct UserPolicy {
+    is_administrator: bool,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct ApiKeys {
+    items: Vec<ApiKey>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct ApiKey {
+    access_token: String,
+    app_name: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct VirtualFolder {
+    name: Option<String>,
+}
+
+impl InitArgs {
+    /// Checks that the flags work together, before anything is asked or sent to the server.
+    pub(crate) fn check(&self) -> Result<(), String> {
+        if self.api_key.is_some() && (self.login_user.is_some() || self.password_stdin) {
+            return Err("--api-key can't be used with --login-user or --password-stdin".into());
+        }
+
+        if !self.non_interactive {
+            return Ok(());
+        }
+
+        if self.url.is_none() {
+            return Err("--url is needed with --non-interactive".into());
+        }
+
+        if self.api_key.is_none() && self.login_user.is_none() {
+            return Err("either --api-key or --login-user is needed with --non-interactive".into());
+        }
+
+        if self.login_user.is_some() && !self.password_stdin {
+            return Err(
+                "--password-stdin is needed with --login-user and --non-interactive".into(),
+            );
+        }
+
+        Ok(())
+    }
+}
+
+/// Asks questions and talks to the jellyfin server to write a working config to `output`.
+pub fn run(args: InitArgs, output: &str) -> Result<(), Box<dyn std::error::Error>> {
+    if Path::new(output).exists() && !args.force {
+        return Err(format!("{} already exists, use --force to overwrite it", output).into());
+    }
+
+    args.check()?;
+
+    let prompter = Prompter::new(!args.non_interactive, std::io::stdin().lock());
+
+    let url = match &args.url {
+        Some(url) => url.clone(),
+        None => prompter.ask("Jellyfin server URL", None)?,
+    };
+    let url = url.trim_end_matches('/').to_string() + "/";
+
+    let http = http_client(args.self_signed, None)?;
+
+    let info: PublicSystemInfo = http
+        .get(format!("{}System/Info/Public", url))
+        .send()?
+        .error_for_status()?
+        .json()
+        .map_err(|err| format!("{} doesn't look like a jellyfin server: {}", url, err))?;
+
+    info!(
+        "Found {} running jellyfin {}",
+        info.server_name.unwrap_or_default().green(),
+        info.version.unwrap_or_default()
+    );
+
+    let (api_key, login_user, session) = match &args.api_key {
+        Some(api_key) => (api_key.clone(), None, false),
+        None => {
+            let user = match &args.login_user {
+                Some(user) => user.clone(),
+                None => prompter.ask("Jellyfin username to log in with", None)?,
+            };
+            // The password is never taken as an argument, those end up in the shell history
+            let password = if args.password_stdin || !std::io::stdin().is_terminal() {
+                prompter.read_line()?
+            } else {
+                rpassword::prompt_password("Password: ")?
+            };
+
+            let (api_key, session) = login(&http, &url, args.self_signed, &user, &password)?;
+            (api_key, Some(user), session)
+        }
+    };
+
+    let http = http_client(args.self_signed, Some(&api_key))?;
+
+    let usernames = if !args.usernames.is_empty() {
+        args.usernames.clone()
+    } else {
+        let users: Vec<String> = match http.get(format!("{}Users", url)).send()?.error_for_status()
+        {
+            Ok(response) => response
+                .json::<Vec<User>>()?
+                .into_iter()
+                .map(|user| user.name)
+                .collect(),
+            // Only administrators can list every user
+            Err(err) => {
+                debug!("Couldn't list users: {}", err);
+                login_user.iter().cloned().collect()
+            }
+        };
+
+        prompter.select(
+            "Whose activity should be shown?",
+            &users,
+            login_user.iter().cloned().collect(),
+        )?
+    };
+
+    if usernames.is_empty() {
+        return Err("at least one username is needed".into());
+    }
+
+    let blacklist = if !args.blacklist_libraries.is_empty() || !prompter.interactive {
+        args.blacklist_libraries.clone()
+    } else {
+        match http
+            .get(format!("{}Library/VirtualFolders", url))
+            .send()?
+            .error_for_status()
+        {
+            Ok(response) => {
+                let libraries: Vec<String> = response
+                    .json::<Vec<VirtualFolder>>()?
+                    .into_iter()
+                    .filter_map(|library| library.name)
+                    .collect();
+
+                prompter.select(
+                    "Which libraries should never be shown? (leave empty for none)",
+                    &libraries,
+                    Vec::new(),
+                )?
+            }
+            Err(err) => {
+                warn!("Couldn't list libraries, skipping the blacklist: {}", err);
+                Vec::new()
+            }
+        }
+    };
+
+    let mut jellyfin = serde_json::json!({
+        "url": url,
+        "api_key": api_key,
+        "username": usernames,
+        "self_signed_cert": args.self_signed,
+    });
+
+    if !blacklist.is_empty() {
+        jellyfin["blacklist"] = serde_json::json!({ "libraries": blacklist });
+    }
+
+    let config = serde_json::json!({
+        "version": crate::migrate::CURRENT_VERSION,
+        "jellyfin": jellyfin,
+    });
+
+    if let Some(parent) = Path::new(output).parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    std::fs::write(output, ConfigFormat::from_path(output).serialize(&config)?)?;
+
+    info!("Wrote config to {}", output.green());
+
+    if session {
+        warn!(
+            "The api_key in {} is the login session of {}, it stops working when that session is \
+             signed out or expires. Ask an administrator for an api key to replace it",
+            output,
+            login_user.unwrap_or_default()
+        );
+    }
+
+    Ok(())
+}
+
+fn http_client(
+    self_signed: bool,
+    token: Option<&str>,
+) -> Result<Client, Box<dyn std::error::Error>> {
+    let mut authorization = format!(
+        "MediaBrowser Client=\"Jellyfin-RPC\", Device=\"Jellyfin-RPC\", DeviceId=\"jellyfin-rpc-init\", Version=\"{}\"",
+        VERSION.unwrap_or("UNKNOWN")
+    );
+
+    if let Some(token) = token {
+        authorization += &format!(", Token=\"{}\"", token);
+    }
+
+    let mut headers = HeaderMap::new();
+    headers.insert(AUTHORIZATION, HeaderValue::from_str(&authorization)?);
+
+    Ok(Client::builder()
+        .default_headers(headers)
+        .danger_accept_invalid_certs(self_signed)
+        .build()?)
+}
+
+/// Logs in and returns an api key, administrators get a new "Jellyfin-RPC" key
+/// while everyone else has to use the token of the login session.
+///
+/// The returned bool is whether it's the token of the session.
+fn login(
+    http: &Client,
+    url: &str,
+    self_signed: bool,
+    user: &str,
+    password: &str,
+) -> Result<(String, bool), Box<dyn std::error::Error>> {
+    let auth: AuthenticationResult = http
+        .post(format!("{}Users/AuthenticateByName", url))
+        .json(&serde_json::json!({ "Username": user, "Pw": password }))
+        .send()?
+        .error_for_status()
+        .map_err(|err| format!("couldn't log in as {}: {}", user, err))?
+        .json()?;
+
+    info!("Logged in as {}", auth.user.name.green());
+
+    if !auth
+        .user
+        .policy
+        .is_some_and(|policy| policy.is_administrator)
+    {
+        warn!(
+            "{} isn't an administrator, using the login session instead of an api key",
+            user
+        );
+        return Ok((auth.access_token, true));
+    }
+
+    let http = http_client(self_signed, Some(&auth.access_token))?;
+
+    http.post(format!("{}Auth/Keys", url))
+        .query(&[("App", "Jellyfin-RPC")])
+        .send()?
+        .error_for_status()?;
+
+    let keys: ApiKeys = http
+        .get(format!("{}Auth/Keys", url))
+        .send()?
+        .error_for_status()?
+        .json()?;
+
+    let key = keys
+        .items
+        .into_iter()
+        .rev()
+        .find(|key| key.app_name.as_deref() == Some("Jellyfin-RPC"))
+        .ok_or("the created api key couldn't be found")?;
+
+    info!("Created an api key called \"Jellyfin-RPC\"");
+
+    Ok((key.access_token, false))
+}
+
+/// Asks questions on stdout and reads the answers from `input`.
+pub(crate) struct Prompter<R> {
+    interactive: bool,
+    input: RefCell<R>,
+}
+
+impl<R: BufRead> Prompter<R> {
+    pub(crate) fn new(interactive: bool, input: R) -> Self {
+        Self {
+            interactive,
+            input: RefCell::new(input),
+        }
+    }
+
+    /// Reads one line from the input, without the line break.
+    fn read_line(&self) -> Result<String, Box<dyn std::error::Error>> {
+        let mut line = String::new();
+        if self.input.borrow_mut().read_line(&mut line)? == 0 {
+            return Err("no more input".into());
+        }
+
+        Ok(line.trim_end_matches(['\r', '\n']).to_string())
+    }
+
+    pub(crate) fn ask(
+        &self,
+        question: &str,
+        default: Option<&str>,
+    ) -> Result<String, Box<dyn std::error::Error>> {
+        if !self.interactive {
+            return default
+                .map(String::from)
+                .ok_or_else(|| format!("missing value for: {}", question).into());
+        }
+
+        loop {
+            match default {
+                Some(default) => print!("{} [{}]: ", question, default),
+                None => print!("{}: ", question),
+            }
+            std::io::stdout().flush()?;
+
+            let answer = self.read_line()?;
+            let answer = answer.trim();
+
+            match (answer.is_empty(), default) {
+                (false, _) => return Ok(answer.to_string()),
+                (true, Some(default)) => return Ok(default.to_string()),
+                (true, None) => continue,
+            }
+        }
+    }
+
+    /// Lets the user pick any number of options by number or name, separated by commas.
+    pub(crate) fn select(
+        &self,
+        question: &str,
+        options: &[String],
+        default: Vec<String>,
+    ) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+        if !self.interactive {
+            return Ok(default);
+        }
+
+        println!("{}", question);
+        for (i, option) in options.iter().enumerate() {
+            println!("  {}) {}", i + 1, option);
+        }
+
+        let answer = self.ask(
+            "Numbers or names separated by commas",
+            Some(&default.join(",")),
+        )?;
+
+        Ok(answer
+            .split(',')
+            .map(|choice| choice.trim())
+            .filter(|choice| !choice.is_empty())
+            .map(|choice| match choice.parse::<usize>() {
+                Ok(i) if (1..=options.len()).contains(&i) => options[i - 1].clone(),
+                _ => choice.to_string(),
+            })
+            .collect())
+    }
+}
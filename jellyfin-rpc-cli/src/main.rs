@@ -1,15 +1,29 @@
-use clap::Parser;
+use clap::{Parser, Subcommand};
 use colored::Colorize;
-use config::{get_config_path, get_urls_path, Config};
-use jellyfin_rpc::{Client, DisplayFormat, EpisodeDisplayOptions, VERSION};
-use log::{debug, error, info};
+use config::{get_config_path, get_urls_path, Config, JellyfinServer};
+use jellyfin_rpc::{
+    Client, ClientBuilder, DisplayFormat, EpisodeDisplayOptions, ServerBuilder, VERSION,
+};
+use log::{debug, error, info, warn};
 use retry::retry_with_index;
 use simple_logger::SimpleLogger;
+use std::io::IsTerminal;
+use std::path::Path;
 use std::{thread::sleep, time::Duration};
 use time::macros::format_description;
+use validate::{Diagnostic, Severity};
+use watcher::ConfigWatcher;
 mod config;
+mod init;
+mod migrate;
+mod secrets;
+mod sinks;
+#[cfg(test)]
+mod tests;
 #[cfg(feature = "updates")]
 mod updates;
+mod validate;
+mod watcher;
 
 /*
     TODO: Comments
@@ -22,6 +36,13 @@ mod updates;
 struct Args {
     #[arg(short = 'c', long = "config", help = "Path to the config file")]
     config: Option<String>,
+    #[arg(
+        short = 'p',
+        long = "profile",
+        help = "Name of the config profile to use",
+        value_parser = config::profile_name
+    )]
+    profile: Option<String>,
     #[arg(
         short = 'i',
         long = "image-urls-file",
@@ -42,49 +63,107 @@ struct Args {
         default_value_t = String::from("info")
     )]
     log_level: String,
+    #[arg(
+        short = 's',
+        long = "set",
+        value_name = "KEY=VALUE",
+        help = "Override a config value, for example jellyfin.url=https://example.com"
+    )]
+    set: Vec<String>,
+    #[command(subcommand)]
+    command: Option<Command>,
 }
 
-fn main() -> Result<(), Box<dyn std::error::Error>> {
-    let args = Args::parse();
-
-    if std::env::var("RUST_LOG").is_err() {
-        std::env::set_var("RUST_LOG", args.log_level);
-    }
+#[derive(Subcommand)]
+enum Command {
+    /// Create a config by asking for the server and logging in
+    Init(init::InitArgs),
+    /// Store secrets in the system keyring instead of the config file
+    Secret {
+        #[command(subcommand)]
+        action: SecretAction,
+    },
+    /// Check the config file for mistakes
+    CheckConfig,
+    /// Print the JSON Schema of the config file
+    Schema {
+        #[arg(
+            short = 'o',
+            long = "output",
+            help = "Write the schema to this file instead of printing it"
+        )]
+        output: Option<String>,
+    },
+    /// Upgrade the config file to the current version, the old file is kept as a backup
+    MigrateConfig {
+        #[arg(
+            short = 'f',
+            long = "force",
+            help = "Migrate YAML configs even though their comments are lost"
+        )]
+        force: bool,
+    },
+    /// Convert the config file to JSON, TOML or YAML
+    ConvertConfig {
+        #[arg(
+            help = "Path to write the converted config to, the format is chosen by its extension"
+        )]
+        output: String,
+        #[arg(
+            short = 'f',
+            long = "force",
+            help = "Overwrite the output file if it exists"
+        )]
+        force: bool,
+    },
+}
 
-    SimpleLogger::new()
-        .with_level(log::LevelFilter::Info)
-        .env()
-        .with_timestamp_format(format_description!(
-            "[year]-[month]-[day] [hour]:[minute]:[second]"
-        ))
-        .init()
-        .unwrap();
+#[derive(Subcommand)]
+enum SecretAction {
+    /// Store a secret, it's only used when it isn't set in the config
+    Set {
+        #[arg(value_enum)]
+        secret: secrets::Secret,
+        #[arg(help = "Value of the secret, read from stdin if left out")]
+        value: Option<String>,
+    },
+}
 
-    info!("Initializing Jellyfin-RPC");
+/// Logs every problem found in the config.
+fn report(diagnostics: &[Diagnostic]) {
+    for diagnostic in diagnostics {
+        match diagnostic.severity {
+            Severity::Error => error!("{}", diagnostic),
+            Severity::Warning => warn!("{}", diagnostic),
+        }
+    }
+}
 
-    #[cfg(feature = "updates")]
-    updates::checker();
+/// Loads the config again and applies it to the client without disconnecting from Discord,
+/// the old config is kept if the new one has problems.
+fn reload(
+    client: &mut Client,
+    conf_path: &str,
+    profile: Option<&str>,
+    overrides: &[String],
+    image_urls: &str,
+) {
+    info!("Config changed, reloading");
+    report(&validate::check(conf_path, profile, overrides).unwrap_or_default());
 
-    let conf_path = &args
-        .config
-        .unwrap_or(get_config_path().expect("default config path couldn't be determined"));
+    let result = Config::builder()
+        .load(conf_path, profile, overrides)
+        .and_then(|file| file.build())
+        .and_then(|conf| client.update(client_builder(conf, image_urls.to_string())));
 
-    let conf = match Config::builder().load(conf_path) {
-        Ok(file) => file.build(),
-        Err(error) => {
-            error!(
-                "Config file could not be loaded at path: {}",
-                conf_path.red()
-            );
-            error!("{}", error);
-            error!(
-                "Please create a proper config file: {}",
-                "https://github.com/Radiicall/jellyfin-rpc/wiki/Setup".green()
-            );
-            std::process::exit(1)
-        }
-    };
+    match result {
+        Ok(()) => info!("Reloaded config"),
+        Err(err) => error!("Failed to reload config, keeping the old one: {}", err),
+    }
+}
 
+/// Creates a client builder with every option from the config set.
+fn client_builder(conf: Config, image_urls: String) -> ClientBuilder {
     debug!("Creating jellyfin-rpc client builder");
     let mut builder = Client::builder();
 
@@ -100,7 +179,7 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
         .show_images(conf.images.enable_images)
         .use_imgur(conf.images.imgur_images)
         .large_image_text(format!("Jellyfin-RPC v{}", VERSION.unwrap_or("UNKNOWN")))
-        .imgur_urls_file_location(args.image_urls.unwrap_or(get_urls_path()?));
+        .imgur_urls_file_location(image_urls);
 
     if let Some(display) = conf.jellyfin.music.display {
         debug!("Found config.jellyfin.music.display");
@@ -112,6 +191,11 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
         builder.music_separator(separator);
     }
 
+    if let Some(buttons) = conf.jellyfin.music.buttons {
+        debug!("Found config.jellyfin.music.buttons");
+        builder.music_buttons(buttons);
+    }
+
     if let Some(display) = conf.jellyfin.movies.display {
         debug!("Found config.jellyfin.movies.display");
         builder.movies_display(display);
@@ -122,6 +206,11 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
         builder.movies_separator(separator);
     }
 
+    if let Some(buttons) = conf.jellyfin.movies.buttons {
+        debug!("Found config.jellyfin.movies.buttons");
+        builder.movies_buttons(buttons);
+    }
+
     if let Some(display) = conf.jellyfin.episodes.display {
         debug!("Found config.jellyfin.episodes.display");
         builder.episodes_display(display);
@@ -139,6 +228,16 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
         builder.episodes_separator(separator);
     }
 
+    if let Some(buttons) = conf.jellyfin.episodes.buttons {
+        debug!("Found config.jellyfin.episodes.buttons");
+        builder.episodes_buttons(buttons);
+    }
+
+    if let Some(public_url) = conf.jellyfin.public_url {
+        debug!("Found config.jellyfin.public_url");
+        builder.public_url(public_url);
+    }
+
     if let Some(media_types) = conf.jellyfin.blacklist.media_types {
         debug!("Found config.jellyfin.blacklist.media_types");
         debug!("Blacklisted MediaTypes: {:?}", media_types);
@@ -151,6 +250,60 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
         builder.blacklist_libraries(libraries);
     }
 
+    if let Some(rules) = conf.jellyfin.blacklist.rules {
+        debug!("Found config.jellyfin.blacklist.rules");
+        debug!("Blacklist rules: {:?}", rules);
+        builder.blacklist_rules(rules);
+    }
+
+    if let Some(private_tags) = conf.jellyfin.blacklist.private_tags {
+        debug!("Found config.jellyfin.blacklist.private_tags");
+        debug!("Private tags: {:?}", private_tags);
+        builder.private_tags(private_tags);
+    }
+
+    if let Some(action) = conf.jellyfin.blacklist.action {
+        debug!("Found config.jellyfin.blacklist.action");
+        builder.blacklist_action(action);
+    }
+
+    if let Some(cache_ttl) = conf.jellyfin.blacklist.cache_ttl {
+        debug!("Found config.jellyfin.blacklist.cache_ttl");
+        builder.blacklist_cache_ttl(cache_ttl);
+    }
+
+    if let Some(fail_closed) = conf.jellyfin.blacklist.fail_closed {
+        debug!("Found config.jellyfin.blacklist.fail_closed");
+        builder.blacklist_fail_closed(fail_closed);
+    }
+
+    if let Some(media_types) = conf.jellyfin.whitelist.media_types {
+        debug!("Found config.jellyfin.whitelist.media_types");
+        debug!("Whitelisted MediaTypes: {:?}", media_types);
+        builder.whitelist_media_types(media_types);
+    }
+
+    if let Some(libraries) = conf.jellyfin.whitelist.libraries {
+        debug!("Found config.jellyfin.whitelist.libraries");
+        debug!("Whitelisted libraries: {:?}", libraries);
+        builder.whitelist_libraries(libraries);
+    }
+
+    if let Some(priority) = conf.jellyfin.priority {
+        debug!("Found config.jellyfin.priority");
+        builder.priority(priority);
+    }
+
+    if let Some(kind) = conf.jellyfin.server_kind {
+        debug!("Found config.jellyfin.server_kind");
+        builder.server_kind(kind);
+    }
+
+    for server in conf.jellyfin.servers {
+        debug!("Found a server in config.jellyfin.servers: {}", server.url);
+        builder.server(server_builder(server));
+    }
+
     if let Some(application_id) = conf.discord.application_id {
         debug!("Found config.discord.application_id");
         builder.client_id(application_id);
@@ -161,13 +314,309 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
         builder.buttons(buttons);
     }
 
+    if let Some(dynamic_buttons) = conf.discord.dynamic_buttons {
+        debug!("Found config.discord.dynamic_buttons");
+
+        if let Some(providers) = dynamic_buttons.providers {
+            builder.dynamic_button_providers(providers);
+        }
+
+        if let Some(labels) = dynamic_buttons.labels {
+            builder.dynamic_button_labels(labels);
+        }
+    }
+
+    if let Some(username) = conf.discord.jellyfin_button_user {
+        debug!("Found config.discord.jellyfin_button_user");
+        builder.jellyfin_button_user(username);
+    }
+
+    #[cfg(feature = "transform")]
+    if let Some(transform) = conf.images.transform {
+        debug!("Found config.images.transform");
+        builder.image_transform(transform);
+    }
+
+    if let Some(schedule) = conf.schedule {
+        debug!("Found config.schedule");
+        builder.schedule(schedule);
+    }
+
     if let Some(client_id) = conf.imgur.client_id {
         debug!("Found config.imgur.client_id");
         builder.imgur_client_id(client_id);
     }
 
+    if let Some(outputs) = conf.outputs {
+        if let Some(discord) = outputs.discord {
+            debug!("Found config.outputs.discord");
+            builder.discord(discord);
+        }
+
+        if outputs.stdout.unwrap_or(false) {
+            debug!("Found config.outputs.stdout");
+            builder.sink(sinks::StdoutSink::default());
+        }
+
+        if let Some(path) = outputs.file {
+            debug!("Found config.outputs.file");
+            builder.sink(sinks::FileSink::new(path));
+        }
+
+        if let Some(url) = outputs.webhook {
+            debug!("Found config.outputs.webhook");
+            builder.sink(sinks::WebhookSink::new(url));
+        }
+
+        if let Some(mqtt) = outputs.mqtt {
+            debug!("Found config.outputs.mqtt");
+            #[cfg(feature = "mqtt")]
+            builder.sink(sinks::MqttSink::new(mqtt));
+            #[cfg(not(feature = "mqtt"))]
+            warn!(
+                "Ignoring the mqtt output of {}, jellyfin-rpc was built without the mqtt feature",
+                mqtt.host
+            );
+        }
+    }
+
+    builder
+}
+
+/// Creates a server builder for one of `config.jellyfin.servers`.
+fn server_builder(server: JellyfinServer) -> ServerBuilder {
+    let mut builder = ServerBuilder::new();
+
+    builder
+        .url(server.url)
+        .api_key(server.api_key)
+        .usernames(server.username)
+        .self_signed(server.self_signed_cert);
+
+    if let Some(name) = server.name {
+        builder.name(name);
+    }
+
+    if let Some(public_url) = server.public_url {
+        builder.public_url(public_url);
+    }
+
+    if let Some(priority) = server.priority {
+        builder.priority(priority);
+    }
+
+    if let Some(kind) = server.server_kind {
+        builder.kind(kind);
+    }
+
+    if let Some(username) = server.jellyfin_button_user {
+        builder.jellyfin_button_user(username);
+    }
+
+    if let Some(media_types) = server.blacklist.media_types {
+        debug!("Blacklisted MediaTypes: {:?}", media_types);
+        builder.blacklist_media_types(media_types);
+    }
+
+    if let Some(libraries) = server.blacklist.libraries {
+        debug!("Blacklisted libraries: {:?}", libraries);
+        builder.blacklist_libraries(libraries);
+    }
+
+    if let Some(rules) = server.blacklist.rules {
+        debug!("Blacklist rules: {:?}", rules);
+        builder.blacklist_rules(rules);
+    }
+
+    if let Some(private_tags) = server.blacklist.private_tags {
+        debug!("Private tags: {:?}", private_tags);
+        builder.private_tags(private_tags);
+    }
+
+    if let Some(action) = server.blacklist.action {
+        builder.blacklist_action(action);
+    }
+
+    if let Some(cache_ttl) = server.blacklist.cache_ttl {
+        builder.blacklist_cache_ttl(cache_ttl);
+    }
+
+    if let Some(fail_closed) = server.blacklist.fail_closed {
+        builder.blacklist_fail_closed(fail_closed);
+    }
+
+    if let Some(media_types) = server.whitelist.media_types {
+        debug!("Whitelisted MediaTypes: {:?}", media_types);
+        builder.whitelist_media_types(media_types);
+    }
+
+    if let Some(libraries) = server.whitelist.libraries {
+        debug!("Whitelisted libraries: {:?}", libraries);
+        builder.whitelist_libraries(libraries);
+    }
+
+    builder
+}
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let args = Args::parse();
+
+    if std::env::var("RUST_LOG").is_err() {
+        std::env::set_var("RUST_LOG", args.log_level);
+    }
+
+    SimpleLogger::new()
+        .with_level(log::LevelFilter::Info)
+        .env()
+        .with_timestamp_format(format_description!(
+            "[year]-[month]-[day] [hour]:[minute]:[second]"
+        ))
+        .init()
+        .unwrap();
+
+    let conf_path = &args.config.unwrap_or(
+        get_config_path(args.profile.as_deref())
+            .expect("default config path couldn't be determined"),
+    );
+
+    if let Some(Command::Init(init_args)) = args.command {
+        if let Err(err) = init::run(init_args, conf_path) {
+            error!("{}", err);
+            std::process::exit(1)
+        }
+        return Ok(());
+    }
+
+    if let Some(Command::Secret {
+        action: SecretAction::Set { secret, value },
+    }) = args.command
+    {
+        let Some(store) = secrets::default_store() else {
+            error!("Jellyfin-RPC was built without keyring support");
+            std::process::exit(1)
+        };
+
+        let value = match value {
+            Some(value) => value,
+            // Don't echo the secret when it's typed in, piped values are read as-is
+            None if std::io::stdin().is_terminal() => {
+                rpassword::prompt_password(format!("Enter the value for {}: ", secret.key()))?
+                    .trim()
+                    .to_string()
+            }
+            None => {
+                let mut value = String::new();
+                std::io::stdin().read_line(&mut value)?;
+                value.trim().to_string()
+            }
+        };
+
+        store.set(secret, &value)?;
+        info!("Stored {} in the keyring", secret.key());
+        return Ok(());
+    }
+
+    if let Some(Command::Schema { output }) = args.command {
+        let schema = config::schema()?;
+
+        match output {
+            Some(output) => {
+                std::fs::write(&output, schema)?;
+                info!("Wrote the config schema to {}", output.green());
+            }
+            None => println!("{}", schema),
+        }
+        return Ok(());
+    }
+
+    if let Some(Command::MigrateConfig { force }) = args.command {
+        match migrate::migrate_file(conf_path, force)? {
+            Some(backup) => {
+                info!(
+                    "Upgraded {} to version {}, the old config was kept at {}",
+                    conf_path.green(),
+                    migrate::CURRENT_VERSION,
+                    backup
+                );
+                if config::ConfigFormat::from_path(conf_path) == config::ConfigFormat::Yaml {
+                    warn!(
+                        "The comments in {} weren't kept, copy them over from {}",
+                        conf_path, backup
+                    );
+                }
+            }
+            None => info!("{} is already up to date", conf_path.green()),
+        }
+        return Ok(());
+    }
+
+    if let Some(Command::ConvertConfig { output, force }) = args.command {
+        if Path::new(&output).exists() && !force {
+            error!(
+                "{} already exists, use --force to overwrite it",
+                output.red()
+            );
+            std::process::exit(1)
+        }
+
+        config::convert(conf_path, &output)?;
+        info!("Converted {} to {}", conf_path, output.green());
+        return Ok(());
+    }
+
+    if let Some(Command::CheckConfig) = args.command {
+        let diagnostics = validate::check(conf_path, args.profile.as_deref(), &args.set)?;
+        report(&diagnostics);
+
+        if diagnostics.iter().any(|d| d.severity == Severity::Error) {
+            std::process::exit(1)
+        } else if diagnostics.is_empty() {
+            info!("No problems found in {}", conf_path.green());
+        }
+
+        return Ok(());
+    }
+
+    info!("Initializing Jellyfin-RPC");
+
+    #[cfg(feature = "updates")]
+    updates::checker();
+
+    let diagnostics = if Path::new(conf_path).exists() {
+        validate::check(conf_path, args.profile.as_deref(), &args.set).unwrap_or_default()
+    } else {
+        Vec::new()
+    };
+    report(&diagnostics);
+
+    let conf = match Config::builder()
+        .load(conf_path, args.profile.as_deref(), &args.set)
+        .and_then(|file| file.build())
+    {
+        Ok(conf) => conf,
+        Err(error) => {
+            error!(
+                "Config file could not be loaded at path: {}",
+                conf_path.red()
+            );
+            // The error is already part of the diagnostics if the file itself is broken
+            if !diagnostics.iter().any(|d| d.severity == Severity::Error) {
+                error!("{}", error);
+            }
+            error!(
+                "Please create a proper config file: {}",
+                "https://github.com/Radiicall/jellyfin-rpc/wiki/Setup".green()
+            );
+            std::process::exit(1)
+        }
+    };
+
+    let image_urls = args
+        .image_urls
+        .unwrap_or(get_urls_path(args.profile.as_deref())?);
+
     debug!("Building client");
-    let mut client = builder.build()?;
+    let mut client = client_builder(conf, image_urls.clone()).build()?;
 
     info!("Connecting to Discord");
     retry_with_index(
@@ -186,11 +635,29 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     .unwrap();
     info!("Connected!");
 
+    let watcher = if Path::new(conf_path).exists() {
+        ConfigWatcher::new(conf_path)
+            .inspect_err(|err| warn!("Config changes won't be picked up: {}", err))
+            .ok()
+    } else {
+        None
+    };
+
     let mut currently_playing = String::new();
 
     loop {
         sleep(Duration::from_secs(args.wait_time as u64));
 
+        if watcher.as_ref().is_some_and(|watcher| watcher.changed()) {
+            reload(
+                &mut client,
+                conf_path,
+                args.profile.as_deref(),
+                &args.set,
+                &image_urls,
+            );
+        }
+
         match client.set_activity() {
             Ok(activity) => {
                 if activity.is_empty() && !currently_playing.is_empty() {
@@ -0,0 +1,256 @@
+//! Upgrades old config files to the current shape.
+//!
+//! Configs without a `version` field are version 0. Every migration takes a config
+//! from one version to the next, so old configs are brought up to date one step at a time.
+
+use crate::config::ConfigFormat;
+use jellyfin_rpc::{DisplayFormat, EpisodeDisplayOptions};
+use log::debug;
+use serde_json::{Map, Value};
+
+/// Version of the config written by this version of jellyfin-rpc.
+pub const CURRENT_VERSION: u64 = 1;
+
+/// `MIGRATIONS[n]` upgrades a config from version `n` to `n + 1`.
+const MIGRATIONS: &[fn(&mut Value)] = &[structured_display];
+
+/// The version of the config, 0 if it doesn't have one.
+pub fn version(config: &Value) -> u64 {
+    config.get("version").and_then(Value::as_u64).unwrap_or(0)
+}
+
+/// Upgrades the config to [`CURRENT_VERSION`], returns the version it was upgraded from.
+///
+/// Fails if the config was written by a newer version of jellyfin-rpc.
+pub fn migrate(config: &mut Value) -> Result<u64, String> {
+    let from = version(config);
+
+    if from > CURRENT_VERSION {
+        return Err(format!(
+            "config version {} is newer than the newest version this jellyfin-rpc understands ({})",
+            from, CURRENT_VERSION
+        ));
+    }
+
+    for (version, migration) in MIGRATIONS.iter().enumerate().skip(from as usize) {
+        debug!("Migrating config from version {}", version);
+        migration(config);
+
+        // Profiles hold parts of a config, so they're upgraded the same way
+        if let Some(Value::Object(profiles)) = config.get_mut("profiles") {
+            profiles.values_mut().for_each(migration);
+        }
+    }
+
+    set_version(config, CURRENT_VERSION);
+
+    Ok(from)
+}
+
+/// Rewrites the config file at `path` in the current shape, the old file is kept next to it.
+///
+/// TOML files are edited in place so comments and formatting are kept. YAML files are
+/// written from scratch which loses their comments, so they're only migrated with `force`.
+///
+/// Returns the path of the backup, or `None` if the config was already up to date.
+pub fn migrate_file(path: &str, force: bool) -> Result<Option<String>, Box<dyn std::error::Error>> {
+    let format = ConfigFormat::from_path(path);
+    let data = std::fs::read_to_string(path)?;
+    let old: Value = format.parse(&data)?;
+
+    let mut config = old.clone();
+    let from = migrate(&mut config)?;
+    if from == CURRENT_VERSION {
+        return Ok(None);
+    }
+
+    if format == ConfigFormat::Yaml && !force {
+        return Err(format!(
+            "comments in {} would be lost by migrating it, use --force to migrate it anyway",
+            path
+        )
+        .into());
+    }
+
+    let backup = format!("{}.v{}.bak", path, from);
+    std::fs::copy(path, &backup)?;
+
+    crate::config::remove_nulls(&mut config);
+
+    let migrated = match (format, &old, &config) {
+        (ConfigFormat::Toml, Value::Object(old), Value::Object(new)) => {
+            let mut document: toml_edit::DocumentMut = data.parse()?;
+            update_toml(document.as_table_mut(), old, new);
+
+            // Keep the version at the top, where migrate() puts it too
+            document
+                .sort_values_by(|a, _, b, _| (a.get() != "version").cmp(&(b.get() != "version")));
+
+            document.to_string()
+        }
+        _ => format.serialize(&config)?,
+    };
+    std::fs::write(path, migrated)?;
+
+    Ok(Some(backup))
+}
+
+/// Changes the parts of a TOML table that differ between `old` and `new`,
+/// everything else is left alone with its comments and formatting.
+fn update_toml(
+    table: &mut dyn toml_edit::TableLike,
+    old: &Map<String, Value>,
+    new: &Map<String, Value>,
+) {
+    let removed: Vec<String> = table
+        .iter()
+        .map(|(key, _)| key.to_string())
+        .filter(|key| !new.contains_key(key))
+        .collect();
+
+    for key in removed {
+        table.remove(&key);
+    }
+
+    for (key, value) in new {
+        if old.get(key) == Some(value) {
+            continue;
+        }
+
+        if let (Some(Value::Object(old)), Value::Object(new)) = (old.get(key), value) {
+            if let Some(table) = table
+                .get_mut(key)
+                .and_then(toml_edit::Item::as_table_like_mut)
+            {
+                update_toml(table, old, new);
+                continue;
+            }
+        }
+
+        let Some(value) = toml_value(value) else {
+            table.remove(key);
+            continue;
+        };
+
+        // Replacing the item instead of the whole entry keeps the comments above the key
+        match table.get_mut(key) {
+            Some(item) => *item = toml_edit::Item::Value(value),
+            None => {
+                table.insert(key, toml_edit::Item::Value(value));
+            }
+        }
+    }
+}
+
+/// `None` for `null`, TOML doesn't have it.
+fn toml_value(value: &Value) -> Option<toml_edit::Value> {
+    Some(match value {
+        Value::Null => return None,
+        Value::Bool(value) => (*value).into(),
+        Value::Number(number) => match number.as_i64() {
+            Some(number) => number.into(),
+            None => number.as_f64()?.into(),
+        },
+        Value::String(value) => value.into(),
+        Value::Array(values) => values
+            .iter()
+            .filter_map(toml_value)
+            .collect::<toml_edit::Array>()
+            .into(),
+        Value::Object(map) => map
+            .iter()
+            .filter_map(|(key, value)| Some((key.as_str(), toml_value(value)?)))
+            .collect::<toml_edit::InlineTable>()
+            .into(),
+    })
+}
+
+/// Puts `version` at the top of the config so it's the first thing seen in the file.
+fn set_version(config: &mut Value, version: u64) {
+    let Value::Object(map) = config else {
+        return;
+    };
+
+    let mut versioned = Map::new();
+    versioned.insert("version".to_string(), version.into());
+    versioned.extend(
+        std::mem::take(map)
+            .into_iter()
+            .filter(|(key, _)| key != "version"),
+    );
+
+    *map = versioned;
+}
+
+/// Version 0 to 1: comma separated usernames become a list, `display` lists and strings
+/// become `DisplayFormat` objects and `show_simple`, `append_prefix` and `add_divider`
+/// are replaced by `episodes.display`.
+fn structured_display(config: &mut Value) {
+    let Some(Value::Object(jellyfin)) = config.get_mut("jellyfin") else {
+        return;
+    };
+
+    if let Some(Value::String(username)) = jellyfin.get("username") {
+        let usernames: Vec<Value> = username.split(',').map(Value::from).collect();
+        jellyfin.insert("username".to_string(), Value::Array(usernames));
+    }
+
+    for media in ["music", "movies", "episodes"] {
+        let Some(display) = jellyfin
+            .get_mut(media)
+            .and_then(|media| media.get_mut("display"))
+        else {
+            continue;
+        };
+
+        let format = match display {
+            Value::Array(items) => DisplayFormat::from(
+                items
+                    .iter()
+                    .filter_map(Value::as_str)
+                    .map(String::from)
+                    .collect::<Vec<String>>(),
+            ),
+            Value::String(items) => DisplayFormat::from(items.clone()),
+            _ => continue,
+        };
+
+        *display = serde_json::to_value(format).expect("Unreachable error");
+    }
+
+    let flag = |key: &str| jellyfin.get(key).and_then(Value::as_bool).unwrap_or(false);
+    let legacy = ["show_simple", "append_prefix", "add_divider"]
+        .iter()
+        .any(|key| jellyfin.contains_key(*key));
+
+    let format = DisplayFormat::from(EpisodeDisplayOptions {
+        divider: flag("add_divider"),
+        prefix: flag("append_prefix"),
+        simple: flag("show_simple"),
+    });
+
+    for key in ["show_simple", "append_prefix", "add_divider"] {
+        jellyfin.remove(key);
+    }
+
+    // The legacy options were only used when there was no `episodes.display`
+    let has_display = jellyfin
+        .get("episodes")
+        .and_then(|episodes| episodes.get("display"))
+        .is_some_and(|display| !display.is_null());
+
+    if legacy && !has_display {
+        let episodes = jellyfin
+            .entry("episodes")
+            .or_insert_with(|| Value::Object(Map::new()));
+
+        if !episodes.is_object() {
+            *episodes = Value::Object(Map::new());
+        }
+
+        episodes.as_object_mut().expect("Unreachable error").insert(
+            "display".to_string(),
+            serde_json::to_value(format).expect("Unreachable error"),
+        );
+    }
+}
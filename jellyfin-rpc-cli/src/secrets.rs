@@ -0,0 +1,103 @@
+use clap::ValueEnum;
+use log::{debug, warn};
+
+/// Secrets that can be kept out of the config file.
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+pub enum Secret {
+    /// `jellyfin.api_key`
+    ApiKey,
+    /// `imgur.client_id`
+    ImgurClientId,
+    /// `outputs.mqtt.password`
+    MqttPassword,
+}
+
+impl Secret {
+    /// Name the secret is stored under in the keyring.
+    pub fn key(&self) -> &'static str {
+        match self {
+            Secret::ApiKey => "api_key",
+            Secret::ImgurClientId => "imgur_client_id",
+            Secret::MqttPassword => "mqtt_password",
+        }
+    }
+}
+
+/// Somewhere secrets can be stored outside of the config file.
+pub trait SecretStore {
+    fn get(&self, secret: Secret) -> Result<Option<String>, Box<dyn std::error::Error>>;
+    fn set(&self, secret: Secret, value: &str) -> Result<(), Box<dyn std::error::Error>>;
+}
+
+/// The system keyring, Secret Service over D-Bus on Linux,
+/// Keychain on macOS and Credential Manager on Windows.
+#[cfg(feature = "keyring")]
+pub struct Keyring;
+
+#[cfg(feature = "keyring")]
+impl Keyring {
+    const SERVICE: &'static str = "jellyfin-rpc";
+}
+
+#[cfg(feature = "keyring")]
+impl SecretStore for Keyring {
+    fn get(&self, secret: Secret) -> Result<Option<String>, Box<dyn std::error::Error>> {
+        match keyring::Entry::new(Self::SERVICE, secret.key())?.get_password() {
+            Ok(value) => Ok(Some(value)),
+            Err(keyring::Error::NoEntry) => Ok(None),
+            Err(err) => Err(Box::new(err)),
+        }
+    }
+
+    fn set(&self, secret: Secret, value: &str) -> Result<(), Box<dyn std::error::Error>> {
+        keyring::Entry::new(Self::SERVICE, secret.key())?.set_password(value)?;
+        Ok(())
+    }
+}
+
+/// The secret store used when nothing else is specified,
+/// `None` when jellyfin-rpc is built without keyring support.
+pub fn default_store() -> Option<Box<dyn SecretStore>> {
+    #[cfg(feature = "keyring")]
+    return Some(Box::new(Keyring));
+    #[cfg(not(feature = "keyring"))]
+    return None;
+}
+
+/// Finds a secret, in order of preference:
+/// the value in the config, the contents of `file` and the secret store.
+pub fn resolve(
+    secret: Secret,
+    value: Option<String>,
+    file: Option<&str>,
+    store: Option<&dyn SecretStore>,
+) -> Result<Option<String>, Box<dyn std::error::Error>> {
+    if let Some(value) = value.filter(|value| !value.is_empty()) {
+        return Ok(Some(value));
+    }
+
+    if let Some(file) = file {
+        debug!("Reading {} from {}", secret.key(), file);
+        let value = std::fs::read_to_string(file)
+            .map_err(|err| format!("couldn't read {}: {}", file, err))?;
+
+        // Secret files usually end with a newline
+        return Ok(Some(value.trim().to_string()));
+    }
+
+    let Some(store) = store else {
+        return Ok(None);
+    };
+
+    debug!("Looking for {} in the secret store", secret.key());
+
+    // Not having a keyring available shouldn't stop anything that doesn't need it
+    Ok(store.get(secret).unwrap_or_else(|err| {
+        warn!(
+            "Couldn't read {} from the secret store: {}",
+            secret.key(),
+            err
+        );
+        None
+    }))
+}
@@ -0,0 +1,321 @@
+//! Places other than discord where the activity can be shown, see `outputs` in the config.
+//!
+//! Every sink gets the activity as a JSON object, and `{}` when it's cleared.
+//! Failures are only logged, the client reconnects to discord when a sink fails
+//! which wouldn't help any of these.
+
+use jellyfin_rpc::{Presence, PresenceSink};
+use log::{debug, warn};
+use std::io::Write;
+use std::path::PathBuf;
+
+/// Sent when nothing is playing anymore.
+const CLEARED: &str = "{}";
+
+/// Whether `presence` differs from the last one that was sent and remembers it if it does.
+///
+/// The timestamps move by a second every now and then while playing,
+/// so they only count as a change when they jump, like after seeking.
+pub(crate) fn changed(last: &mut Option<Presence>, presence: &Presence) -> bool {
+    let jumped = |old: Option<i64>, new: Option<i64>| match (old, new) {
+        (Some(old), Some(new)) => (old - new).abs() > 5,
+        (old, new) => old.is_some() != new.is_some(),
+    };
+
+    let changed = match last {
+        Some(last) => {
+            let moved = Presence {
+                start: presence.start,
+                end: presence.end,
+                progress: presence.progress,
+                ..last.clone()
+            };
+
+            moved != *presence
+                || jumped(last.start, presence.start)
+                || jumped(last.end, presence.end)
+        }
+        None => true,
+    };
+
+    if changed {
+        *last = Some(presence.clone());
+    }
+
+    changed
+}
+
+/// Prints the activity to stdout as a line of JSON whenever it changes.
+pub struct StdoutSink {
+    out: Box<dyn Write + Send>,
+    last: Option<Presence>,
+}
+
+impl Default for StdoutSink {
+    fn default() -> Self {
+        Self::with_writer(std::io::stdout())
+    }
+}
+
+impl StdoutSink {
+    /// Writes the lines to `out` instead of stdout.
+    pub(crate) fn with_writer<W: Write + Send + 'static>(out: W) -> Self {
+        Self {
+            out: Box::new(out),
+            last: None,
+        }
+    }
+
+    fn print(&mut self, json: &str) -> Result<(), Box<dyn std::error::Error>> {
+        writeln!(self.out, "{}", json)?;
+        self.out.flush()?;
+
+        Ok(())
+    }
+}
+
+impl PresenceSink for StdoutSink {
+    fn name(&self) -> String {
+        "stdout".to_string()
+    }
+
+    fn set_presence(&mut self, presence: &Presence) -> Result<(), Box<dyn std::error::Error>> {
+        if changed(&mut self.last, presence) {
+            self.print(&serde_json::to_string(presence)?)?;
+        }
+
+        Ok(())
+    }
+
+    fn clear_presence(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        if self.last.take().is_some() {
+            self.print(CLEARED)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Writes the activity to a file, or a named pipe if there is one at the path.
+pub struct FileSink {
+    path: PathBuf,
+}
+
+impl FileSink {
+    pub fn new<T: Into<PathBuf>>(path: T) -> Self {
+        Self { path: path.into() }
+    }
+
+    fn write(&self, json: &str) -> Result<(), Box<dyn std::error::Error>> {
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::{FileTypeExt, OpenOptionsExt};
+
+            if std::fs::metadata(&self.path).is_ok_and(|meta| meta.file_type().is_fifo()) {
+                // Opening a pipe blocks until something reads from it, unless it's non-blocking
+                let pipe = std::fs::OpenOptions::new()
+                    .write(true)
+                    .custom_flags(libc::O_NONBLOCK)
+                    .open(&self.path);
+
+                match pipe {
+                    Ok(mut pipe) => writeln!(pipe, "{}", json)?,
+                    Err(err) => debug!("Nothing is reading {}: {}", self.path.display(), err),
+                }
+
+                return Ok(());
+            }
+        }
+
+        // Readers never see a half written file when it's replaced in one go
+        let mut temp = self.path.clone().into_os_string();
+        temp.push(".tmp");
+
+        std::fs::write(&temp, format!("{}\n", json))?;
+        std::fs::rename(&temp, &self.path)?;
+
+        Ok(())
+    }
+}
+
+impl PresenceSink for FileSink {
+    fn name(&self) -> String {
+        self.path.display().to_string()
+    }
+
+    fn set_presence(&mut self, presence: &Presence) -> Result<(), Box<dyn std::error::Error>> {
+        // Written on every update so status bars can show the progress
+        if let Err(err) = self.write(&serde_json::to_string(presence)?) {
+            warn!(
+                "Couldn't write the activity to {}: {}",
+                self.path.display(),
+                err
+            );
+        }
+
+        Ok(())
+    }
+
+    fn clear_presence(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        if let Err(err) = self.write(CLEARED) {
+            warn!(
+                "Couldn't write the activity to {}: {}",
+                self.path.display(),
+                err
+            );
+        }
+
+        Ok(())
+    }
+}
+
+/// POSTs the activity to a url whenever it changes.
+pub struct WebhookSink {
+    url: String,
+    http: reqwest::blocking::Client,
+    last: Option<Presence>,
+}
+
+impl WebhookSink {
+    pub fn new<T: Into<String>>(url: T) -> Self {
+        Self {
+            url: url.into(),
+            http: reqwest::blocking::Client::new(),
+            last: None,
+        }
+    }
+
+    fn post(&self, json: String) -> Result<(), Box<dyn std::error::Error>> {
+        self.http
+            .post(&self.url)
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .body(json)
+            .send()?
+            .error_for_status()?;
+
+        Ok(())
+    }
+}
+
+impl PresenceSink for WebhookSink {
+    fn name(&self) -> String {
+        self.url.clone()
+    }
+
+    fn set_presence(&mut self, presence: &Presence) -> Result<(), Box<dyn std::error::Error>> {
+        if !changed(&mut self.last, presence) {
+            return Ok(());
+        }
+
+        if let Err(err) = self.post(serde_json::to_string(presence)?) {
+            warn!("Couldn't send the activity to {}: {}", self.url, err);
+            // Try again on the next update
+            self.last = None;
+        }
+
+        Ok(())
+    }
+
+    fn clear_presence(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        self.last = None;
+
+        if let Err(err) = self.post(CLEARED.to_string()) {
+            warn!("Couldn't send the activity to {}: {}", self.url, err);
+        }
+
+        Ok(())
+    }
+}
+
+/// Publishes the activity to an MQTT topic whenever it changes.
+///
+/// The message is retained, so anything subscribing later still gets the current activity.
+#[cfg(feature = "mqtt")]
+pub struct MqttSink {
+    client: rumqttc::Client,
+    topic: String,
+    last: Option<Presence>,
+}
+
+#[cfg(feature = "mqtt")]
+impl MqttSink {
+    pub fn new(config: crate::config::Mqtt) -> Self {
+        use rumqttc::{ConnectionError, Event, MqttOptions};
+        use std::time::Duration;
+
+        let mut options = MqttOptions::new(
+            format!("jellyfin-rpc-{}", std::process::id()),
+            config.host,
+            config.port.unwrap_or(1883),
+        );
+        options.set_keep_alive(Duration::from_secs(30));
+
+        if let Some(username) = config.username {
+            options.set_credentials(username, config.password.unwrap_or_default());
+        }
+
+        let (client, mut connection) = rumqttc::Client::new(options, 10);
+
+        // The connection only makes progress while something polls it
+        std::thread::spawn(move || {
+            for event in connection.iter() {
+                match event {
+                    Ok(Event::Incoming(event)) => debug!("MQTT: {:?}", event),
+                    Ok(Event::Outgoing(_)) => (),
+                    // The sink was dropped, like after the config was reloaded
+                    Err(ConnectionError::RequestsDone) => break,
+                    Err(err) => {
+                        warn!("MQTT connection failed: {}", err);
+                        std::thread::sleep(Duration::from_secs(5));
+                    }
+                }
+            }
+        });
+
+        Self {
+            client,
+            topic: config
+                .topic
+                .unwrap_or_else(|| "jellyfin-rpc/activity".to_string()),
+            last: None,
+        }
+    }
+
+    fn publish(&self, json: String) -> Result<(), Box<dyn std::error::Error>> {
+        self.client
+            .try_publish(&self.topic, rumqttc::QoS::AtLeastOnce, true, json)?;
+
+        Ok(())
+    }
+}
+
+#[cfg(feature = "mqtt")]
+impl PresenceSink for MqttSink {
+    fn name(&self) -> String {
+        format!("mqtt topic {}", self.topic)
+    }
+
+    fn set_presence(&mut self, presence: &Presence) -> Result<(), Box<dyn std::error::Error>> {
+        if !changed(&mut self.last, presence) {
+            return Ok(());
+        }
+
+        if let Err(err) = self.publish(serde_json::to_string(presence)?) {
+            warn!("Couldn't publish the activity to {}: {}", self.topic, err);
+            // Try again on the next update
+            self.last = None;
+        }
+
+        Ok(())
+    }
+
+    fn clear_presence(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        self.last = None;
+
+        if let Err(err) = self.publish(CLEARED.to_string()) {
+            warn!("Couldn't publish the activity to {}: {}", self.topic, err);
+        }
+
+        Ok(())
+    }
+}
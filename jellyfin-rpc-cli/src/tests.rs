@@ -0,0 +1,1008 @@
+use crate::config::{
+    convert, env_overrides, find_config, get_urls_path, override_value, parse_set, profile_name,
+    remove_nulls, schema_types, set_value, Config, ConfigBuilder, ConfigFormat, Display, Username,
+};
+use crate::init::{InitArgs, Prompter};
+use crate::migrate::{migrate, migrate_file, CURRENT_VERSION};
+use crate::secrets::{resolve, Secret, SecretStore};
+use crate::sinks::{changed, FileSink, StdoutSink, WebhookSink};
+use crate::validate::{check_with, Severity};
+use crate::watcher::{handler, ConfigWatcher};
+use clap::Parser;
+use jellyfin_rpc::{MediaType, Presence, PresenceSink};
+use serde_json::json;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::TcpListener;
+use std::sync::{Arc, Mutex};
+
+/// Stand-in for the system keyring
+#[derive(Default)]
+struct MemoryStore(RefCell<HashMap<&'static str, String>>);
+
+impl SecretStore for MemoryStore {
+    fn get(&self, secret: Secret) -> Result<Option<String>, Box<dyn std::error::Error>> {
+        Ok(self.0.borrow().get(secret.key()).cloned())
+    }
+
+    fn set(&self, secret: Secret, value: &str) -> Result<(), Box<dyn std::error::Error>> {
+        self.0.borrow_mut().insert(secret.key(), value.to_string());
+        Ok(())
+    }
+}
+
+#[test]
+fn secret_resolution() {
+    let store = MemoryStore::default();
+    store.set(Secret::ApiKey, "from-store").unwrap();
+
+    let file = std::env::temp_dir().join(format!("jellyfin-rpc-secret-{}", std::process::id()));
+    std::fs::write(&file, "from-file\n").unwrap();
+    let file = file.to_str().unwrap();
+
+    let resolved = |value: Option<&str>, file: Option<&str>, secret: Secret| {
+        resolve(secret, value.map(String::from), file, Some(&store)).unwrap()
+    };
+
+    assert_eq!(
+        resolved(Some("inline"), Some(file), Secret::ApiKey).as_deref(),
+        Some("inline")
+    );
+    assert_eq!(
+        resolved(Some(""), Some(file), Secret::ApiKey).as_deref(),
+        Some("from-file")
+    );
+    assert_eq!(
+        resolved(None, None, Secret::ApiKey).as_deref(),
+        Some("from-store")
+    );
+    assert_eq!(resolved(None, None, Secret::ImgurClientId), None);
+    assert!(resolve(Secret::ApiKey, None, Some("/nonexistent/secret"), None).is_err());
+
+    let config: ConfigBuilder = serde_json::from_value(json!({
+        "jellyfin": { "url": "https://example.com", "api_key": "abc", "username": "me" },
+        "outputs": { "mqtt": { "host": "localhost", "username": "me", "password_file": file } }
+    }))
+    .unwrap();
+    let mqtt = config.build().unwrap().outputs.unwrap().mqtt.unwrap();
+    assert_eq!(mqtt.password.as_deref(), Some("from-file"));
+
+    std::fs::remove_file(file).unwrap();
+}
+
+#[test]
+fn config_diagnostics() {
+    let path = std::env::temp_dir().join(format!("jellyfin-rpc-check-{}.toml", std::process::id()));
+    std::fs::write(
+        &path,
+        r#"[jellyfin]
+url = "example.com"
+api_key = "abc"
+username = "me"
+add_divider = true
+colour = "red"
+
+[jellyfin.music]
+display = { details_text = "{track} {artist}" }
+
+[jellyfin.blacklist]
+media_types = ["movies"]
+
+[outputs]
+discord = false
+webhook = "localhost:8123"
+"#,
+    )
+    .unwrap();
+
+    let diagnostics = check_with(path.to_str().unwrap(), None, &[], std::iter::empty()).unwrap();
+    std::fs::remove_file(&path).unwrap();
+
+    let find = |path: &str| {
+        diagnostics
+            .iter()
+            .find(|d| d.path == path)
+            .unwrap_or_else(|| panic!("no diagnostic for {}", path))
+    };
+
+    assert_eq!(find("jellyfin.url").severity, Severity::Error);
+    assert_eq!(find("jellyfin.url").location, Some((2, 1)));
+    assert_eq!(find("jellyfin.colour").location, Some((6, 1)));
+    assert_eq!(find("jellyfin.add_divider").severity, Severity::Warning);
+    assert!(find("jellyfin.music.display.details_text")
+        .message
+        .contains("{artist}"));
+    assert!(find("jellyfin.blacklist.media_types.0")
+        .message
+        .contains("movies"));
+    assert_eq!(find("outputs.webhook").severity, Severity::Error);
+    assert_eq!(diagnostics.len(), 6);
+}
+
+#[test]
+fn config_locations() {
+    let toml = r#"[jellyfin]
+url = "https://example.com"
+api_key = "abc"
+username = "me"
+
+[jellyfin.music.display]
+details_text = "{track}"
+
+[jellyfin.movies.display]
+details_text = "{nope}"
+
+[[jellyfin.episodes.buttons]]
+name = "Home"
+url = "https://example.com"
+
+[[jellyfin.episodes.buttons]]
+name = "Bad"
+url = "not a url"
+"#;
+
+    let yaml = r#"jellyfin:
+  url: https://example.com
+  api_key: abc
+  username: me
+  music:
+    display:
+      details_text: "{track}"
+  movies:
+    display:
+      details_text: "{nope}"
+  episodes:
+    buttons:
+      - name: Home
+        url: https://example.com
+      - name: Bad
+        url: not a url
+"#;
+
+    // Indented with tabs, which YAML doesn't allow outside of JSON style
+    let json = r#"{
+    "jellyfin": {
+        "url": "https://example.com",
+        "api_key": "abc",
+        "username": "me",
+        "music": { "display": { "details_text": "{track}" } },
+        "movies": { "display": { "details_text": "{nope}" } },
+        "episodes": {
+            "buttons": [
+                { "name": "Home", "url": "https://example.com" },
+                { "name": "Bad", "url": "not a url" }
+            ]
+        }
+    }
+}
+"#
+    .replace("    ", "\t");
+
+    for (extension, data, display, button) in [
+        ("toml", toml, (10, 1), (18, 1)),
+        ("yaml", yaml, (10, 7), (16, 9)),
+        ("json", &json, (7, 28), (11, 22)),
+    ] {
+        let path = std::env::temp_dir().join(format!(
+            "jellyfin-rpc-locations-{}.{}",
+            std::process::id(),
+            extension
+        ));
+        std::fs::write(&path, data).unwrap();
+
+        let diagnostics =
+            check_with(path.to_str().unwrap(), None, &[], std::iter::empty()).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        let location = |path: &str| {
+            diagnostics
+                .iter()
+                .find(|d| d.path == path)
+                .unwrap_or_else(|| panic!("no diagnostic for {} in {}", path, extension))
+                .location
+        };
+
+        assert_eq!(diagnostics.len(), 2, "{}", extension);
+        // details_text is under music as well
+        assert_eq!(
+            location("jellyfin.movies.display.details_text"),
+            Some(display),
+            "{}",
+            extension
+        );
+        assert_eq!(
+            location("jellyfin.episodes.buttons.1.url"),
+            Some(button),
+            "{}",
+            extension
+        );
+    }
+}
+
+#[test]
+fn config_diagnostics_overrides() {
+    let path = std::env::temp_dir().join(format!(
+        "jellyfin-rpc-check-overrides-{}.toml",
+        std::process::id()
+    ));
+    std::fs::write(
+        &path,
+        r#"[jellyfin]
+url = "https://example.com"
+api_key = "abc"
+username = "me"
+
+[[jellyfin.servers]]
+url = "example.org"
+api_key = "def"
+username = "you"
+"#,
+    )
+    .unwrap();
+    let path = path.to_str().unwrap();
+
+    let diagnostics = check_with(path, None, &[], std::iter::empty()).unwrap();
+    assert_eq!(diagnostics.len(), 1);
+    // The url of the server, not the one of the main server
+    assert_eq!(diagnostics[0].path, "jellyfin.servers.0.url");
+    assert_eq!(diagnostics[0].location, Some((7, 1)));
+
+    let diagnostics = check_with(
+        path,
+        None,
+        &["outputs.webhook=localhost:8123".to_string()],
+        [(
+            "JELLYFIN_RPC_JELLYFIN__SERVERS__0__URL".to_string(),
+            "https://example.org".to_string(),
+        )]
+        .into_iter(),
+    )
+    .unwrap();
+    assert_eq!(diagnostics.len(), 1);
+    assert_eq!(diagnostics[0].path, "outputs.webhook");
+    assert_eq!(diagnostics[0].severity, Severity::Error);
+    assert_eq!(diagnostics[0].location, None);
+
+    let diagnostics = check_with(
+        path,
+        None,
+        &["jellyfin.nope=1".to_string()],
+        std::iter::empty(),
+    )
+    .unwrap();
+    assert!(diagnostics[0].message.contains("jellyfin.nope"));
+
+    std::fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn config_watcher() {
+    let dir = std::env::temp_dir().join(format!("jellyfin-rpc-watch-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("main.json");
+    std::fs::write(&path, "{}").unwrap();
+
+    let watcher = ConfigWatcher::new(path.to_str().unwrap()).unwrap();
+    assert!(!watcher.changed());
+
+    std::fs::write(&path, "{ }").unwrap();
+    let deadline = std::time::Instant::now() + std::time::Duration::from_secs(10);
+    while !watcher.changed() {
+        assert!(
+            std::time::Instant::now() < deadline,
+            "the change wasn't noticed"
+        );
+        std::thread::sleep(std::time::Duration::from_millis(20));
+    }
+
+    std::fs::remove_dir_all(dir).unwrap();
+
+    // Only events for the config file itself count
+    let (sender, changes) = std::sync::mpsc::channel();
+    let handle = handler(sender, Some("main.json".into()));
+    let event = |kind, file: &str| {
+        notify::Event::new(kind).add_path(std::path::PathBuf::from("/config").join(file))
+    };
+
+    handle(Ok(event(
+        notify::EventKind::Create(notify::event::CreateKind::File),
+        "urls.json",
+    )));
+    handle(Ok(event(
+        notify::EventKind::Remove(notify::event::RemoveKind::File),
+        "main.json",
+    )));
+    assert_eq!(changes.try_iter().count(), 0);
+
+    handle(Ok(event(
+        notify::EventKind::Modify(notify::event::ModifyKind::Any),
+        "main.json",
+    )));
+    assert_eq!(changes.try_iter().count(), 1);
+}
+
+#[test]
+fn config_migration() {
+    let mut config = json!({
+        "jellyfin": {
+            "url": "https://example.com",
+            "username": "me,you",
+            "show_simple": true,
+            "append_prefix": true,
+            "music": { "display": "genres,year", "separator": "-" },
+            "movies": { "display": ["genres"] }
+        }
+    });
+
+    assert_eq!(migrate(&mut config), Ok(0));
+    assert_eq!(
+        config
+            .as_object()
+            .unwrap()
+            .keys()
+            .next()
+            .map(String::as_str),
+        Some("version")
+    );
+    assert_eq!(config["version"], json!(CURRENT_VERSION));
+    assert_eq!(config["jellyfin"]["username"], json!(["me", "you"]));
+    assert_eq!(
+        config["jellyfin"]["music"]["display"]["state_text"],
+        json!("{__default}{genres} {sep} {year}")
+    );
+    assert_eq!(config["jellyfin"]["music"]["separator"], json!("-"));
+    assert_eq!(
+        config["jellyfin"]["movies"]["display"]["state_text"],
+        json!("{__default}{genres}")
+    );
+    assert_eq!(
+        config["jellyfin"]["episodes"]["display"]["state_text"],
+        json!("S{season-padded}E{episode-padded}")
+    );
+    assert!(config["jellyfin"].get("show_simple").is_none());
+
+    let migrated = config.clone();
+    assert_eq!(migrate(&mut config), Ok(CURRENT_VERSION));
+    assert_eq!(config, migrated);
+
+    assert!(migrate(&mut json!({ "version": CURRENT_VERSION + 1 })).is_err());
+
+    let path =
+        std::env::temp_dir().join(format!("jellyfin-rpc-migrate-{}.json", std::process::id()));
+    let path = path.to_str().unwrap();
+    std::fs::write(path, r#"{ "jellyfin": { "username": "me" } }"#).unwrap();
+
+    let backup = migrate_file(path, false).unwrap().unwrap();
+    assert_eq!(
+        std::fs::read_to_string(&backup).unwrap(),
+        r#"{ "jellyfin": { "username": "me" } }"#
+    );
+    assert!(std::fs::read_to_string(path)
+        .unwrap()
+        .contains("\"version\": 1"));
+    assert_eq!(migrate_file(path, false).unwrap(), None);
+
+    std::fs::remove_file(path).unwrap();
+    std::fs::remove_file(backup).unwrap();
+}
+
+#[test]
+fn config_migration_comments() {
+    let toml = r#"# Where my server lives
+[jellyfin]
+url = "https://example.com" # behind the reverse proxy
+username = "me,you"
+show_simple = true
+
+# Songs
+[jellyfin.music]
+display = "genres"
+separator = "-"
+"#;
+
+    let path = |extension| {
+        std::env::temp_dir()
+            .join(format!(
+                "jellyfin-rpc-migrate-comments-{}.{}",
+                std::process::id(),
+                extension
+            ))
+            .to_str()
+            .unwrap()
+            .to_string()
+    };
+
+    let toml_path = path("toml");
+    std::fs::write(&toml_path, toml).unwrap();
+
+    let backup = migrate_file(&toml_path, false).unwrap().unwrap();
+    let migrated = std::fs::read_to_string(&toml_path).unwrap();
+
+    assert!(migrated.starts_with("version = 1\n"));
+    for kept in [
+        "# Where my server lives\n[jellyfin]\n",
+        "url = \"https://example.com\" # behind the reverse proxy\n",
+        "# Songs\n[jellyfin.music]\n",
+        "separator = \"-\"\n",
+    ] {
+        assert!(
+            migrated.contains(kept),
+            "{} is missing from:\n{}",
+            kept,
+            migrated
+        );
+    }
+    assert!(!migrated.contains("show_simple"));
+
+    // The result is the same as migrating the parsed config
+    let mut expected: serde_json::Value = toml::from_str(toml).unwrap();
+    migrate(&mut expected).unwrap();
+    remove_nulls(&mut expected);
+    assert_eq!(
+        toml::from_str::<serde_json::Value>(&migrated).unwrap(),
+        expected
+    );
+
+    std::fs::remove_file(&toml_path).unwrap();
+    std::fs::remove_file(backup).unwrap();
+
+    // YAML can't be edited in place, so it's only rewritten when forced
+    let yaml_path = path("yaml");
+    let yaml = "# Where my server lives\njellyfin:\n  username: me\n";
+    std::fs::write(&yaml_path, yaml).unwrap();
+
+    assert!(migrate_file(&yaml_path, false).is_err());
+    assert_eq!(std::fs::read_to_string(&yaml_path).unwrap(), yaml);
+
+    let backup = migrate_file(&yaml_path, true).unwrap().unwrap();
+    assert_eq!(std::fs::read_to_string(&backup).unwrap(), yaml);
+
+    std::fs::remove_file(&yaml_path).unwrap();
+    std::fs::remove_file(backup).unwrap();
+}
+
+#[test]
+fn config_conversion() {
+    assert_eq!(ConfigFormat::from_path("main.json"), ConfigFormat::Json);
+    assert_eq!(ConfigFormat::from_path("main.TOML"), ConfigFormat::Toml);
+    assert_eq!(ConfigFormat::from_path("main.yml"), ConfigFormat::Yaml);
+    assert_eq!(ConfigFormat::from_path("main"), ConfigFormat::Json);
+
+    let mut value = json!({
+        "jellyfin": {
+            "public_url": null,
+            "blacklist": { "rules": [{ "tags": null, "genres": ["Horror"] }, null] }
+        }
+    });
+    remove_nulls(&mut value);
+    assert_eq!(
+        value,
+        json!({ "jellyfin": { "blacklist": { "rules": [{ "genres": ["Horror"] }] } } })
+    );
+
+    // JSON -> TOML -> YAML -> JSON keeps every value of the example
+    let dir = std::env::temp_dir().join(format!("jellyfin-rpc-convert-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let example = concat!(env!("CARGO_MANIFEST_DIR"), "/../example.json");
+    let path = |name: &str| dir.join(name).to_str().unwrap().to_string();
+
+    convert(example, &path("main.toml")).unwrap();
+    convert(&path("main.toml"), &path("main.yaml")).unwrap();
+    convert(&path("main.yaml"), &path("main.json")).unwrap();
+
+    let read = |path: &str| -> serde_json::Value {
+        ConfigFormat::from_path(path)
+            .parse(&std::fs::read_to_string(path).unwrap())
+            .unwrap()
+    };
+
+    let toml = read(&path("main.toml"));
+    assert_eq!(toml, read(example));
+    assert_eq!(read(&path("main.yaml")), toml);
+    assert_eq!(read(&path("main.json")), toml);
+
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn config_overrides() {
+    let schema = serde_json::to_value(schemars::schema_for!(ConfigBuilder)).unwrap();
+    let key = |key: &str| -> Vec<String> { key.split('.').map(str::to_string).collect() };
+
+    assert_eq!(
+        parse_set("jellyfin.api_key=a=b").unwrap(),
+        (key("jellyfin.api_key"), "a=b".to_string())
+    );
+    assert!(parse_set("jellyfin.api_key").is_err());
+
+    // Values are only parsed as JSON when the field takes that type
+    let value = |path: &str, value: &str| {
+        let types = schema_types(&schema, &key(path)).unwrap();
+        override_value(value, &types, None)
+    };
+
+    assert_eq!(value("jellyfin.username", "1234"), json!("1234"));
+    assert_eq!(
+        value("jellyfin.username", r#"["a", "b"]"#),
+        json!(["a", "b"])
+    );
+    assert_eq!(value("jellyfin.api_key", "1234"), json!("1234"));
+    assert_eq!(value("jellyfin.api_key", r#""quoted""#), json!("quoted"));
+    assert_eq!(
+        value("discord.application_id", "1053747938519679018"),
+        json!("1053747938519679018")
+    );
+    assert_eq!(value("outputs.mqtt.port", "1883"), json!(1883));
+    assert_eq!(value("images.enable_images", "true"), json!(true));
+    assert_eq!(value("jellyfin.servers.0.priority", "-1"), json!(-1));
+    assert_eq!(
+        value("profiles.friend.jellyfin.api_key", "1234"),
+        json!("1234")
+    );
+    assert_eq!(
+        value("jellyfin.blacklist.media_types", "movie"),
+        json!("movie")
+    );
+    assert!(schema_types(&schema, &key("jellyfin.colour")).is_none());
+
+    let mut config = json!({ "jellyfin": { "servers": [{ "url": "a" }] } });
+    set_value(&mut config, &key("jellyfin.servers.0.url"), json!("b"));
+    set_value(&mut config, &key("discord.show_paused"), json!(false));
+    assert_eq!(
+        config,
+        json!({
+            "jellyfin": { "servers": [{ "url": "b" }] },
+            "discord": { "show_paused": false }
+        })
+    );
+
+    let vars = [
+        ("JELLYFIN_RPC_JELLYFIN__API_KEY", "env"),
+        ("JELLYFIN_RPC_JELLYFIN__USERNAME", "1234"),
+        ("JELLYFIN_RPC_LOG_FILE", "/tmp/log"),
+        ("HOME", "/home/me"),
+    ]
+    .map(|(name, value)| (name.to_string(), value.to_string()));
+
+    assert_eq!(
+        env_overrides(vars.clone().into_iter(), &schema)
+            .into_iter()
+            .map(|(key, _)| key.join("."))
+            .collect::<Vec<String>>(),
+        vec!["jellyfin.api_key", "jellyfin.username"]
+    );
+
+    // The file is overridden by environment variables, which are overridden by --set
+    let path = std::env::temp_dir().join(format!(
+        "jellyfin-rpc-overrides-{}.json",
+        std::process::id()
+    ));
+    std::fs::write(
+        &path,
+        r#"{ "jellyfin": { "url": "https://example.com", "username": "me", "api_key": "file" } }"#,
+    )
+    .unwrap();
+    let path = path.to_str().unwrap();
+
+    let load = |sets: &[&str]| {
+        let sets: Vec<String> = sets.iter().map(|set| set.to_string()).collect();
+        Config::builder()
+            .load_with(path, None, &sets, vars.clone().into_iter())
+            .unwrap()
+    };
+
+    let config = load(&[]);
+    assert_eq!(config.jellyfin.api_key, "env");
+    assert!(matches!(config.jellyfin.username, Username::String(ref name) if name == "1234"));
+
+    let config = load(&["jellyfin.api_key=cli"]);
+    assert_eq!(config.jellyfin.api_key, "cli");
+
+    assert!(Config::builder()
+        .load_with(
+            path,
+            None,
+            &["jellyfin.colour=red".to_string()],
+            std::iter::empty()
+        )
+        .is_err());
+
+    std::fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn config_schema() {
+    let schema: serde_json::Value =
+        serde_json::from_str(&crate::config::schema().unwrap()).unwrap();
+
+    assert!(schema.pointer("/properties/$schema").is_some());
+    assert_eq!(schema.pointer("/required/0"), Some(&json!("jellyfin")));
+    assert!(schema
+        .pointer("/$defs/MediaType/enum")
+        .and_then(|types| types.as_array())
+        .unwrap()
+        .contains(&json!("movie")));
+    for name in ["DisplayFormat", "Button", "ContentRule", "BlacklistAction"] {
+        assert!(schema["$defs"].get(name).is_some(), "{} is missing", name);
+    }
+
+    // Every key in the example config has to be known to the schema
+    let example: serde_json::Value =
+        serde_json::from_str(include_str!("../../example.json")).unwrap();
+    for key in example["jellyfin"].as_object().unwrap().keys() {
+        assert!(
+            schema
+                .pointer(&format!("/$defs/JellyfinBuilder/properties/{}", key))
+                .is_some(),
+            "jellyfin.{} is missing",
+            key
+        );
+    }
+
+    let config: crate::config::ConfigBuilder = serde_json::from_value(json!({
+        "$schema": "./schema.json",
+        "jellyfin": { "url": "https://example.com", "username": ["me"] }
+    }))
+    .unwrap();
+    assert_eq!(config.schema.as_deref(), Some("./schema.json"));
+}
+
+#[test]
+fn config_profiles() {
+    let dir = std::env::temp_dir().join(format!("jellyfin-rpc-profiles-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+
+    let path = dir.join("main.json");
+    let path = path.to_str().unwrap();
+    std::fs::write(
+        path,
+        json!({
+            "jellyfin": {
+                "url": "https://home.example.com",
+                "username": ["me"],
+                "music": { "separator": "-" }
+            },
+            "profiles": {
+                "friend": {
+                    "jellyfin": {
+                        "url": "https://friend.example.com",
+                        "music": { "display": "genres" }
+                    }
+                }
+            }
+        })
+        .to_string(),
+    )
+    .unwrap();
+
+    let home = Config::builder().load(path, None, &[]).unwrap();
+    assert_eq!(home.jellyfin.url, "https://home.example.com");
+    assert_eq!(home.profiles, None);
+
+    let friend = Config::builder().load(path, Some("friend"), &[]).unwrap();
+    assert_eq!(friend.jellyfin.url, "https://friend.example.com");
+    assert_eq!(
+        friend.jellyfin.username,
+        Username::Vec(vec!["me".to_string()])
+    );
+    let music = friend.jellyfin.music.unwrap();
+    assert_eq!(music.separator.as_deref(), Some("-"));
+    assert!(matches!(music.display, Some(Display::CustomFormat(_))));
+
+    let err = Config::builder()
+        .load(path, Some("work"), &[])
+        .unwrap_err()
+        .to_string();
+    assert!(err.contains("friend"), "{}", err);
+
+    // A profile's own file doesn't need a profiles section
+    let work = dir.join("main-work.json");
+    let work = work.to_str().unwrap();
+    std::fs::write(
+        work,
+        r#"{ "jellyfin": { "url": "https://work.example.com", "username": "me" } }"#,
+    )
+    .unwrap();
+    assert!(Config::builder().load(work, Some("work"), &[]).is_ok());
+
+    // Profiles inside main.json are used before falling back to main-<profile>.json
+    let config_dir = dir.to_str().unwrap().to_string() + "/";
+    assert_eq!(
+        find_config(&config_dir, Some("work")).unwrap(),
+        config_dir.clone() + "main-work.json"
+    );
+    std::fs::remove_file(work).unwrap();
+    assert_eq!(
+        find_config(&config_dir, Some("work")).unwrap(),
+        config_dir.clone() + "main.json"
+    );
+    std::fs::remove_file(path).unwrap();
+    assert_eq!(
+        find_config(&config_dir, Some("friend")).unwrap(),
+        config_dir.clone() + "main-friend.json"
+    );
+    assert_eq!(
+        find_config(&config_dir, None).unwrap(),
+        config_dir.clone() + "main.json"
+    );
+
+    for profile in ["../x", "a/b", "a\\b", "..", ""] {
+        assert!(profile_name(profile).is_err(), "{} was accepted", profile);
+        assert!(find_config(&config_dir, Some(profile)).is_err());
+        assert!(get_urls_path(Some(profile)).is_err());
+    }
+    assert_eq!(profile_name("friend-2").unwrap(), "friend-2");
+
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+fn movie_presence() -> Presence {
+    Presence {
+        details: "Up".to_string(),
+        state: "2009".to_string(),
+        image_text: "Jellyfin-RPC".to_string(),
+        image_url: "https://i.imgur.com/oX6vcds.png".to_string(),
+        media_type: MediaType::Movie,
+        paused: false,
+        start: Some(1000),
+        end: Some(6600),
+        progress: Some(0.1),
+        buttons: Vec::new(),
+    }
+}
+
+/// Stand-in for stdout that can still be read after the sink took it
+#[derive(Clone, Default)]
+struct SharedBuffer(Arc<Mutex<Vec<u8>>>);
+
+impl Write for SharedBuffer {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.lock().unwrap().write(buf)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Answers one request per connection with each of the `statuses` in turn,
+/// the bodies of the requests are returned once they're all answered.
+fn http_server(statuses: Vec<u16>) -> (String, std::thread::JoinHandle<Vec<String>>) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}/hook", listener.local_addr().unwrap());
+
+    let handle = std::thread::spawn(move || {
+        statuses
+            .into_iter()
+            .map(|status| {
+                let mut stream = BufReader::new(listener.accept().unwrap().0);
+                let mut length = 0;
+
+                loop {
+                    let mut line = String::new();
+                    stream.read_line(&mut line).unwrap();
+                    if line == "\r\n" {
+                        break;
+                    }
+                    if let Some((name, value)) = line.split_once(':') {
+                        if name.eq_ignore_ascii_case("content-length") {
+                            length = value.trim().parse().unwrap();
+                        }
+                    }
+                }
+
+                let mut body = vec![0; length];
+                stream.read_exact(&mut body).unwrap();
+                write!(
+                    stream.get_mut(),
+                    "HTTP/1.1 {} Status\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+                    status
+                )
+                .unwrap();
+
+                String::from_utf8(body).unwrap()
+            })
+            .collect()
+    });
+
+    (url, handle)
+}
+
+#[test]
+fn sink_changes() {
+    let presence = movie_presence();
+    let mut last = None;
+
+    assert!(changed(&mut last, &presence));
+    assert!(!changed(&mut last, &presence));
+
+    // Time passing moves the timestamps and progress a little
+    let moved = Presence {
+        start: Some(1002),
+        end: Some(6602),
+        progress: Some(0.11),
+        ..presence.clone()
+    };
+    assert!(!changed(&mut last, &moved));
+
+    // Seeking makes them jump
+    let seeked = Presence {
+        start: Some(400),
+        end: Some(6000),
+        ..presence.clone()
+    };
+    assert!(changed(&mut last, &seeked));
+
+    let paused = Presence {
+        paused: true,
+        start: None,
+        end: None,
+        ..seeked.clone()
+    };
+    assert!(changed(&mut last, &paused));
+    assert!(!changed(&mut last, &paused));
+
+    let renamed = Presence {
+        details: "Up (2009)".to_string(),
+        ..paused
+    };
+    assert!(changed(&mut last, &renamed));
+    assert_eq!(last, Some(renamed));
+}
+
+#[test]
+fn stdout_sink() {
+    let out = SharedBuffer::default();
+    let mut sink = StdoutSink::with_writer(out.clone());
+    let presence = movie_presence();
+
+    // Nothing was shown yet, so there's nothing to clear
+    sink.clear_presence().unwrap();
+    sink.set_presence(&presence).unwrap();
+    sink.set_presence(&presence).unwrap();
+    sink.clear_presence().unwrap();
+    sink.clear_presence().unwrap();
+
+    let written = String::from_utf8(out.0.lock().unwrap().clone()).unwrap();
+    let lines: Vec<&str> = written.lines().collect();
+    assert_eq!(lines.len(), 2);
+    assert_eq!(
+        serde_json::from_str::<serde_json::Value>(lines[0]).unwrap()["details"],
+        "Up"
+    );
+    assert_eq!(lines[1], "{}");
+}
+
+#[test]
+fn webhook_sink() {
+    let (url, server) = http_server(vec![500, 200, 200]);
+    let mut sink = WebhookSink::new(url);
+    let presence = movie_presence();
+
+    // The first one fails so it's sent again, after that only changes are sent
+    sink.set_presence(&presence).unwrap();
+    sink.set_presence(&presence).unwrap();
+    sink.set_presence(&presence).unwrap();
+    sink.clear_presence().unwrap();
+
+    let bodies = server.join().unwrap();
+    assert_eq!(bodies.len(), 3);
+    assert_eq!(bodies[0], bodies[1]);
+    assert_eq!(
+        serde_json::from_str::<serde_json::Value>(&bodies[0]).unwrap()["state"],
+        "2009"
+    );
+    assert_eq!(bodies[2], "{}");
+}
+
+#[test]
+fn file_sink() {
+    let dir = std::env::temp_dir().join(format!("jellyfin-rpc-sink-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("activity.json");
+
+    let presence = movie_presence();
+
+    let mut sink = FileSink::new(&path);
+    sink.set_presence(&presence).unwrap();
+
+    let written: serde_json::Value =
+        serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+    assert_eq!(written["details"], "Up");
+    assert_eq!(written["media_type"], "Movie");
+    assert_eq!(written["end"], 6600);
+
+    sink.clear_presence().unwrap();
+    assert_eq!(std::fs::read_to_string(&path).unwrap(), "{}\n");
+
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn init_prompts() {
+    let options = vec!["alice".to_string(), "bob".to_string(), "carol".to_string()];
+
+    let prompter = Prompter::new(true, "3, dave,,9\n\n".as_bytes());
+    assert_eq!(
+        prompter
+            .select("Whose activity?", &options, vec!["alice".to_string()])
+            .unwrap(),
+        ["carol", "dave", "9"]
+    );
+    // An empty answer takes the default
+    assert_eq!(
+        prompter
+            .select("Whose activity?", &options, vec!["alice".to_string()])
+            .unwrap(),
+        ["alice"]
+    );
+    assert!(prompter
+        .select("Whose activity?", &options, Vec::new())
+        .is_err());
+
+    let prompter = Prompter::new(false, "2\n".as_bytes());
+    assert_eq!(
+        prompter
+            .select("Whose activity?", &options, vec!["bob".to_string()])
+            .unwrap(),
+        ["bob"]
+    );
+    assert!(prompter.ask("Jellyfin server URL", None).is_err());
+}
+
+#[test]
+fn init_flags() {
+    #[derive(clap::Parser)]
+    struct Cli {
+        #[command(flatten)]
+        init: InitArgs,
+    }
+
+    let check = |args: &[&str]| {
+        Cli::try_parse_from(["init"].iter().chain(args))
+            .unwrap()
+            .init
+            .check()
+    };
+
+    assert!(check(&[]).is_ok());
+    assert!(check(&[
+        "--non-interactive",
+        "--url",
+        "https://example.com",
+        "--api-key",
+        "abc"
+    ])
+    .is_ok());
+    assert!(check(&[
+        "--non-interactive",
+        "--url",
+        "https://example.com",
+        "--login-user",
+        "me",
+        "--password-stdin"
+    ])
+    .is_ok());
+
+    assert!(check(&["--non-interactive", "--api-key", "abc"])
+        .unwrap_err()
+        .contains("--url"));
+    assert!(
+        check(&["--non-interactive", "--url", "https://example.com"])
+            .unwrap_err()
+            .contains("--login-user")
+    );
+    assert!(check(&[
+        "--non-interactive",
+        "--url",
+        "https://example.com",
+        "--login-user",
+        "me"
+    ])
+    .unwrap_err()
+    .contains("--password-stdin"));
+    assert!(check(&["--api-key", "abc", "--login-user", "me"]).is_err());
+    // The password can't be given as an argument
+    assert!(Cli::try_parse_from(["init", "--password", "hunter2"]).is_err());
+}
@@ -0,0 +1,628 @@
+use crate::config::{
+    apply_overrides, apply_profile, collect_overrides, merge, ConfigBuilder, ConfigFormat,
+};
+use crate::migrate;
+use jellyfin_rpc::{BUTTON_PLACEHOLDERS, DISPLAY_PLACEHOLDERS};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::{env, fmt};
+use yaml_rust2::parser::{Event, MarkedEventReceiver};
+use yaml_rust2::scanner::Marker;
+
+/// Names that are understood as a `MediaType`.
+const MEDIA_TYPES: &[&str] = &[
+    "movie",
+    "episode",
+    "music",
+    "audio",
+    "livetv",
+    "tvchannel",
+    "book",
+    "audiobook",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Severity {
+    /// The config can't be used.
+    Error,
+    /// The config works but probably doesn't do what was intended.
+    Warning,
+}
+
+/// A problem found in the config file.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    /// Path to the field, like `jellyfin.music.display`.
+    pub path: String,
+    pub message: String,
+    /// Line and column of the field in the file, both starting at 1.
+    pub location: Option<(usize, usize)>,
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.location {
+            Some((line, column)) => write!(
+                f,
+                "{} (line {}, column {}): {}",
+                self.path, line, column, self.message
+            ),
+            None if self.path.is_empty() => write!(f, "{}", self.message),
+            None => write!(f, "{}: {}", self.path, self.message),
+        }
+    }
+}
+
+/// Checks the config file at `path` and returns every problem that was found.
+///
+/// The `profile`, environment variables and `overrides` are applied the same way
+/// as when the config is loaded. Only fails if the file can't be read.
+pub fn check(
+    path: &str,
+    profile: Option<&str>,
+    overrides: &[String],
+) -> Result<Vec<Diagnostic>, Box<dyn std::error::Error>> {
+    check_with(path, profile, overrides, env::vars())
+}
+
+/// Same as [check] with `vars` instead of the environment variables.
+pub fn check_with(
+    path: &str,
+    profile: Option<&str>,
+    overrides: &[String],
+    vars: impl Iterator<Item = (String, String)>,
+) -> Result<Vec<Diagnostic>, Box<dyn std::error::Error>> {
+    let data = std::fs::read_to_string(path)?;
+    let format = ConfigFormat::from_path(path);
+
+    let mut checker = Checker {
+        locations: locations(&data, format),
+        overridden: Vec::new(),
+        diagnostics: Vec::new(),
+    };
+
+    let mut config: Value = match format.parse(&data) {
+        Ok(config) => config,
+        Err(err) => {
+            checker.error("", err.to_string());
+            return Ok(checker.diagnostics);
+        }
+    };
+
+    // Parsing straight from the text gives the location of type errors
+    if let Err(err) = format.parse::<ConfigBuilder>(&data) {
+        checker.error("", err.to_string());
+    }
+
+    let unknown = unknown_keys(config.clone());
+    for path in &unknown {
+        checker.warning(path, "unknown key, it will be ignored".to_string());
+    }
+
+    if let Some(Value::Object(profiles)) = config.get("profiles") {
+        for (name, profile) in profiles {
+            let path = format!("profiles.{}", name);
+            let mut merged = config.clone();
+            if let Some(map) = merged.as_object_mut() {
+                map.remove("profiles");
+            }
+            merge(&mut merged, profile.clone());
+
+            if let Err(err) = serde_json::from_value::<ConfigBuilder>(merged.clone()) {
+                checker.error(&path, err.to_string());
+            }
+
+            for key in unknown_keys(merged) {
+                if !unknown.contains(&key) {
+                    checker.warning(
+                        &format!("{}.{}", path, key),
+                        "unknown key, it will be ignored".to_string(),
+                    );
+                }
+            }
+        }
+    }
+
+    // The rest is checked on the config that will actually be used
+    let schema = serde_json::to_value(schemars::schema_for!(ConfigBuilder))?;
+    let overrides = match collect_overrides(overrides, vars, &schema) {
+        Ok(overrides) => overrides,
+        Err(err) => {
+            checker.error("", err.to_string());
+            return Ok(checker.diagnostics);
+        }
+    };
+
+    if let Err(err) = apply_profile(&mut config, path, profile) {
+        checker.error("", err);
+        return Ok(checker.diagnostics);
+    }
+    apply_overrides(&mut config, &overrides, &schema);
+
+    if !overrides.is_empty() {
+        if let Err(err) = serde_json::from_value::<ConfigBuilder>(config.clone()) {
+            checker.error("", err.to_string());
+        }
+    }
+    checker.overridden = overrides
+        .into_iter()
+        .map(|(key, _)| key.join("."))
+        .collect();
+
+    if migrate::version(&config) > migrate::CURRENT_VERSION {
+        checker.error(
+            "version",
+            format!(
+                "written by a newer jellyfin-rpc, this one only understands up to version {}",
+                migrate::CURRENT_VERSION
+            ),
+        );
+    }
+
+    checker.check_jellyfin(&config);
+    checker.check_outputs(&config);
+    checker.check_buttons("discord.buttons", config.pointer("/discord/buttons"));
+
+    if let Some(size) = config
+        .pointer("/images/transform/size")
+        .and_then(Value::as_u64)
+    {
+        if size > 1024 {
+            checker.warning(
+                "images.transform.size",
+                "images are at most 1024 pixels, that size will be used instead".to_string(),
+            );
+        }
+    }
+
+    if let Some(Value::Array(displays)) = config.pointer("/schedule/displays") {
+        for (i, display) in displays.iter().enumerate() {
+            for media in ["music", "movies", "episodes"] {
+                checker.check_display_format(
+                    &format!("schedule.displays.{}.{}", i, media),
+                    display.get(media),
+                );
+            }
+        }
+    }
+
+    Ok(checker.diagnostics)
+}
+
+/// Paths of every key that isn't part of the config.
+fn unknown_keys(config: Value) -> Vec<String> {
+    let mut unknown = Vec::new();
+    let _: Result<ConfigBuilder, _> =
+        serde_ignored::deserialize(config, |path| unknown.push(path.to_string()));
+    unknown
+}
+
+struct Checker {
+    locations: HashMap<String, (usize, usize)>,
+    /// Paths set by overrides, they aren't in the file.
+    overridden: Vec<String>,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl Checker {
+    fn push(&mut self, severity: Severity, path: &str, message: String) {
+        let overridden = self.overridden.iter().any(|key| {
+            path.strip_prefix(key.as_str())
+                .is_some_and(|rest| rest.is_empty() || rest.starts_with('.'))
+        });
+
+        self.diagnostics.push(Diagnostic {
+            severity,
+            path: path.to_string(),
+            message,
+            location: if overridden {
+                None
+            } else {
+                self.locations.get(path).copied()
+            },
+        });
+    }
+
+    fn error(&mut self, path: &str, message: String) {
+        self.push(Severity::Error, path, message)
+    }
+
+    fn warning(&mut self, path: &str, message: String) {
+        self.push(Severity::Warning, path, message)
+    }
+
+    fn check_jellyfin(&mut self, config: &Value) {
+        let Some(jellyfin) = config.get("jellyfin") else {
+            return;
+        };
+
+        self.check_server("jellyfin", jellyfin);
+
+        if let Some(Value::Array(servers)) = jellyfin.get("servers") {
+            for (i, server) in servers.iter().enumerate() {
+                self.check_server(&format!("jellyfin.servers.{}", i), server);
+            }
+        }
+
+        for key in ["show_simple", "append_prefix", "add_divider"] {
+            if jellyfin.get(key).is_some() {
+                self.warning(
+                    &format!("jellyfin.{}", key),
+                    "deprecated, use jellyfin.episodes.display instead or run migrate-config"
+                        .into(),
+                );
+            }
+        }
+
+        for media in ["music", "movies", "episodes"] {
+            let display = jellyfin
+                .get(media)
+                .and_then(|options| options.get("display"));
+            let path = format!("jellyfin.{}.display", media);
+
+            match display {
+                Some(Value::Array(_)) | Some(Value::String(_)) => self.warning(
+                    &path,
+                    "deprecated, use an object with details_text, state_text and image_text instead \
+                     or run migrate-config"
+                        .into(),
+                ),
+                display => self.check_display_format(&path, display),
+            }
+
+            self.check_buttons(
+                &format!("jellyfin.{}.buttons", media),
+                jellyfin
+                    .get(media)
+                    .and_then(|options| options.get("buttons")),
+            );
+        }
+    }
+
+    fn check_outputs(&mut self, config: &Value) {
+        let Some(outputs) = config.get("outputs") else {
+            return;
+        };
+
+        if let Some(Value::String(value)) = outputs.get("webhook") {
+            match url::Url::parse(value) {
+                Ok(url) if ["http", "https"].contains(&url.scheme()) => (),
+                Ok(_) => self.error(
+                    "outputs.webhook",
+                    "url has to start with http:// or https://".into(),
+                ),
+                Err(err) => self.error("outputs.webhook", format!("invalid url: {}", err)),
+            }
+        }
+
+        if !cfg!(feature = "mqtt") && outputs.get("mqtt").is_some() {
+            self.warning(
+                "outputs.mqtt",
+                "jellyfin-rpc was built without the mqtt feature, it will be ignored".into(),
+            );
+        }
+
+        let enabled = |key: &str| match outputs.get(key) {
+            Some(Value::Bool(enabled)) => *enabled,
+            Some(_) => true,
+            None => key == "discord",
+        };
+
+        if !["discord", "stdout", "file", "webhook", "mqtt"]
+            .iter()
+            .any(|key| enabled(key))
+        {
+            self.warning(
+                "outputs",
+                "every output is turned off, the activity won't be shown anywhere".into(),
+            );
+        }
+    }
+
+    /// Checks the urls and lists of the main server or one of `jellyfin.servers`.
+    fn check_server(&mut self, path: &str, server: &Value) {
+        for key in ["url", "public_url"] {
+            if let Some(Value::String(value)) = server.get(key) {
+                let path = format!("{}.{}", path, key);
+                match url::Url::parse(value) {
+                    Ok(url) if ["http", "https"].contains(&url.scheme()) => (),
+                    Ok(_) => self.error(&path, "url has to start with http:// or https://".into()),
+                    Err(err) => self.error(&path, format!("invalid url: {}", err)),
+                }
+            }
+        }
+
+        for list in ["blacklist", "whitelist"] {
+            self.check_media_types(
+                &format!("{}.{}.media_types", path, list),
+                server.get(list).and_then(|list| list.get("media_types")),
+            );
+        }
+
+        if let Some(Value::Array(rules)) = server.pointer("/blacklist/rules") {
+            for (i, rule) in rules.iter().enumerate() {
+                self.check_rule(&format!("{}.blacklist.rules.{}", path, i), rule);
+            }
+        }
+    }
+
+    fn check_rule(&mut self, path: &str, rule: &Value) {
+        self.check_media_types(&format!("{}.media_types", path), rule.get("media_types"));
+
+        for key in ["any", "all"] {
+            if let Some(Value::Array(rules)) = rule.get(key) {
+                for (i, rule) in rules.iter().enumerate() {
+                    self.check_rule(&format!("{}.{}.{}", path, key, i), rule);
+                }
+            }
+        }
+    }
+
+    fn check_media_types(&mut self, path: &str, media_types: Option<&Value>) {
+        let Some(Value::Array(media_types)) = media_types else {
+            return;
+        };
+
+        for (i, media_type) in media_types.iter().enumerate() {
+            if let Value::String(media_type) = media_type {
+                if !MEDIA_TYPES.contains(&media_type.to_lowercase().as_str()) {
+                    self.warning(
+                        &format!("{}.{}", path, i),
+                        format!(
+                            "unknown media type \"{}\", expected one of: {}",
+                            media_type,
+                            MEDIA_TYPES.join(", ")
+                        ),
+                    );
+                }
+            }
+        }
+    }
+
+    fn check_display_format(&mut self, path: &str, display: Option<&Value>) {
+        let Some(Value::Object(display)) = display else {
+            return;
+        };
+
+        for key in ["details_text", "state_text", "image_text"] {
+            if let Some(Value::String(text)) = display.get(key) {
+                self.check_placeholders(&format!("{}.{}", path, key), text, DISPLAY_PLACEHOLDERS);
+            }
+        }
+    }
+
+    fn check_buttons(&mut self, path: &str, buttons: Option<&Value>) {
+        let Some(Value::Array(buttons)) = buttons else {
+            return;
+        };
+
+        for (i, button) in buttons.iter().enumerate() {
+            let path = format!("{}.{}", path, i);
+
+            for key in ["name", "url"] {
+                if let Some(Value::String(text)) = button.get(key) {
+                    self.check_placeholders(
+                        &format!("{}.{}", path, key),
+                        text,
+                        BUTTON_PLACEHOLDERS,
+                    );
+                }
+            }
+
+            if let Some(Value::String(url)) = button.get("url") {
+                if !["dynamic", "jellyfin"].contains(&url.as_str()) && !url.contains('{') {
+                    if let Err(err) = url::Url::parse(url) {
+                        self.error(&format!("{}.url", path), format!("invalid url: {}", err));
+                    }
+                }
+            }
+        }
+    }
+
+    fn check_placeholders(&mut self, path: &str, text: &str, known: &[&str]) {
+        for placeholder in placeholders(text) {
+            if !known.contains(&placeholder) {
+                self.warning(
+                    path,
+                    format!(
+                        "unknown placeholder {}, it will be shown as-is",
+                        placeholder
+                    ),
+                );
+            }
+        }
+    }
+}
+
+/// Every `{placeholder}` in the text.
+fn placeholders(text: &str) -> Vec<&str> {
+    let mut found = Vec::new();
+    let mut rest = text;
+
+    while let Some(start) = rest.find('{') {
+        let Some(len) = rest[start..].find('}') else {
+            break;
+        };
+
+        let placeholder = &rest[start..=start + len];
+        let name = &placeholder[1..placeholder.len() - 1];
+
+        if !name.is_empty()
+            && name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+        {
+            found.push(placeholder);
+        }
+
+        rest = &rest[start + 1..];
+    }
+
+    found
+}
+
+/// Line and column of every key and array element in the file, both starting at 1,
+/// by the path they'd have in a [Diagnostic].
+///
+/// JSON is read as YAML since it's a subset of it, files that can't be parsed have no locations.
+fn locations(data: &str, format: ConfigFormat) -> HashMap<String, (usize, usize)> {
+    match format {
+        ConfigFormat::Toml => {
+            let mut offsets = HashMap::new();
+            if let Ok(document) = toml_edit::ImDocument::parse(data) {
+                toml_table(document.as_table(), "", &mut offsets);
+            }
+
+            offsets
+                .into_iter()
+                .map(|(path, offset)| (path, line_column(data, offset)))
+                .collect()
+        }
+        ConfigFormat::Json | ConfigFormat::Yaml => {
+            let mut receiver = YamlLocations::default();
+            match yaml_rust2::parser::Parser::new_from_str(data).load(&mut receiver, false) {
+                Ok(()) => receiver.locations,
+                Err(_) => HashMap::new(),
+            }
+        }
+    }
+}
+
+fn join(path: &str, key: impl fmt::Display) -> String {
+    if path.is_empty() {
+        key.to_string()
+    } else {
+        format!("{}.{}", path, key)
+    }
+}
+
+fn line_column(data: &str, offset: usize) -> (usize, usize) {
+    let line = data[..offset].matches('\n').count() + 1;
+    let line_start = data[..offset].rfind('\n').map_or(0, |i| i + 1);
+    let column = data[line_start..offset].chars().count() + 1;
+
+    (line, column)
+}
+
+fn toml_table(table: &toml_edit::Table, path: &str, offsets: &mut HashMap<String, usize>) {
+    for (key, item) in table.iter() {
+        let path = join(path, key);
+
+        if let Some(span) = table.key(key).and_then(|key| key.span()) {
+            offsets.insert(path.clone(), span.start);
+        }
+
+        match item {
+            toml_edit::Item::Table(table) => toml_table(table, &path, offsets),
+            toml_edit::Item::ArrayOfTables(tables) => {
+                for (i, table) in tables.iter().enumerate() {
+                    let path = join(&path, i);
+                    if let Some(span) = table.span() {
+                        offsets.insert(path.clone(), span.start);
+                    }
+                    toml_table(table, &path, offsets);
+                }
+            }
+            toml_edit::Item::Value(value) => toml_value(value, &path, offsets),
+            toml_edit::Item::None => (),
+        }
+    }
+}
+
+fn toml_value(value: &toml_edit::Value, path: &str, offsets: &mut HashMap<String, usize>) {
+    match value {
+        toml_edit::Value::InlineTable(table) => {
+            for (key, value) in table.iter() {
+                let path = join(path, key);
+                if let Some(span) = table.key(key).and_then(|key| key.span()) {
+                    offsets.insert(path.clone(), span.start);
+                }
+                toml_value(value, &path, offsets);
+            }
+        }
+        toml_edit::Value::Array(values) => {
+            for (i, value) in values.iter().enumerate() {
+                let path = join(path, i);
+                if let Some(span) = value.span() {
+                    offsets.insert(path.clone(), span.start);
+                }
+                toml_value(value, &path, offsets);
+            }
+        }
+        _ => (),
+    }
+}
+
+/// Mapping or sequence the YAML parser is in.
+enum YamlNode {
+    /// `key` is the path of the value that comes next, `None` while waiting for a key.
+    Mapping {
+        path: String,
+        key: Option<String>,
+    },
+    Sequence {
+        path: String,
+        index: usize,
+    },
+}
+
+#[derive(Default)]
+struct YamlLocations {
+    nodes: Vec<YamlNode>,
+    locations: HashMap<String, (usize, usize)>,
+}
+
+impl MarkedEventReceiver for YamlLocations {
+    fn on_event(&mut self, event: Event, mark: Marker) {
+        let location = (mark.line(), mark.col() + 1);
+
+        let path = match &event {
+            Event::Scalar(..)
+            | Event::MappingStart(..)
+            | Event::SequenceStart(..)
+            | Event::Alias(_) => {
+                match self.nodes.last_mut() {
+                    None => String::new(),
+                    Some(YamlNode::Mapping { path, key }) => match key.take() {
+                        Some(value_path) => value_path,
+                        None => {
+                            // Keys that aren't plain text can't be part of a path
+                            let name = match &event {
+                                Event::Scalar(name, ..) => name.clone(),
+                                _ => "?".to_string(),
+                            };
+                            let key_path = join(path, name);
+                            self.locations.insert(key_path.clone(), location);
+                            *key = Some(key_path.clone());
+
+                            // A mapping or sequence as key still has to be tracked until it ends
+                            if !matches!(event, Event::MappingStart(..) | Event::SequenceStart(..))
+                            {
+                                return;
+                            }
+                            key_path
+                        }
+                    },
+                    Some(YamlNode::Sequence { path, index }) => {
+                        let item_path = join(path, *index);
+                        *index += 1;
+                        self.locations.insert(item_path.clone(), location);
+                        item_path
+                    }
+                }
+            }
+            Event::MappingEnd | Event::SequenceEnd => {
+                self.nodes.pop();
+                return;
+            }
+            _ => return,
+        };
+
+        match event {
+            Event::MappingStart(..) => self.nodes.push(YamlNode::Mapping { path, key: None }),
+            Event::SequenceStart(..) => self.nodes.push(YamlNode::Sequence { path, index: 0 }),
+            _ => (),
+        }
+    }
+}
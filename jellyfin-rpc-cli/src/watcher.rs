@@ -0,0 +1,86 @@
+use log::{debug, warn};
+use notify::{EventKind, PollWatcher, RecommendedWatcher, RecursiveMode, Watcher};
+use std::ffi::OsString;
+use std::path::Path;
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::time::Duration;
+
+/// Watches the config file for changes.
+///
+/// Uses the native file watcher of the platform (inotify on Linux)
+/// and falls back to polling when that isn't available.
+pub struct ConfigWatcher {
+    // Stops watching when dropped
+    _watcher: Box<dyn Watcher>,
+    changes: Receiver<()>,
+}
+
+impl ConfigWatcher {
+    pub fn new(path: &str) -> notify::Result<Self> {
+        let path = Path::new(path);
+        let file_name = path.file_name().map(|name| name.to_os_string());
+
+        // Editors often replace the file instead of writing to it,
+        // so the directory is watched instead of the file itself
+        let directory = match path.parent() {
+            Some(parent) if !parent.as_os_str().is_empty() => parent,
+            _ => Path::new("."),
+        };
+
+        let (sender, changes) = channel();
+
+        let mut watcher: Box<dyn Watcher> = match RecommendedWatcher::new(
+            handler(sender.clone(), file_name.clone()),
+            Default::default(),
+        ) {
+            Ok(watcher) => Box::new(watcher),
+            Err(err) => {
+                warn!("Falling back to polling for config changes: {}", err);
+                Box::new(PollWatcher::new(
+                    handler(sender, file_name),
+                    notify::Config::default().with_poll_interval(Duration::from_secs(2)),
+                )?)
+            }
+        };
+
+        watcher.watch(directory, RecursiveMode::NonRecursive)?;
+        debug!("Watching {} for config changes", directory.display());
+
+        Ok(Self {
+            _watcher: watcher,
+            changes,
+        })
+    }
+
+    /// Whether the config changed since the last time this was called.
+    pub fn changed(&self) -> bool {
+        // Saving a file usually causes several events, they're all handled as one change
+        self.changes.try_iter().count() > 0
+    }
+}
+
+pub(crate) fn handler(
+    sender: Sender<()>,
+    file_name: Option<OsString>,
+) -> impl Fn(notify::Result<notify::Event>) + Send + 'static {
+    move |event: notify::Result<notify::Event>| {
+        let Ok(event) = event else {
+            return;
+        };
+
+        if !matches!(
+            event.kind,
+            EventKind::Create(_) | EventKind::Modify(_) | EventKind::Any
+        ) {
+            return;
+        }
+
+        if event
+            .paths
+            .iter()
+            .any(|path| path.file_name().map(|name| name.to_os_string()) == file_name)
+        {
+            let _ = sender.send(());
+        }
+    }
+}
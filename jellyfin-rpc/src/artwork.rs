@@ -0,0 +1,227 @@
+use std::io::Cursor;
+
+use image::{imageops, imageops::FilterType, DynamicImage, ImageFormat, Pixel, Rgba, RgbaImage};
+use serde::{Deserialize, Serialize};
+
+use crate::JfResult;
+
+const PROGRESS_COLOR: Rgba<u8> = Rgba([170, 92, 195, 255]);
+const RATING_COLOR: Rgba<u8> = Rgba([255, 193, 7, 255]);
+const INACTIVE_COLOR: Rgba<u8> = Rgba([90, 90, 90, 255]);
+const SHADE_COLOR: Rgba<u8> = Rgba([0, 0, 0, 160]);
+
+/// Discord never shows the image any bigger, larger ones only take longer to upload.
+const MAX_IMAGE_SIZE: u32 = 1024;
+
+/// Separates the progress from the rest of [`ImageTransform::cache_key`].
+const PROGRESS_KEY: &str = "-p";
+
+/// Options for the local image pipeline that runs before an image is uploaded.
+///
+/// Discord displays the large image as a square,
+/// so posters and thumbnails are made square before anything else is applied.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(default)]
+pub struct ImageTransform {
+    /// How the image should be made square.
+    ///
+    /// Defaults to `SquareMode::Crop`.
+    pub square: SquareMode,
+    /// Fill the padding around the image with a blurred copy of itself instead of black.
+    /// Only used with `SquareMode::Pad`.
+    ///
+    /// Defaults to `false`.
+    pub blur_backdrop: bool,
+    /// Draw a bar at the bottom of the image showing how far into the media you are.
+    ///
+    /// Defaults to `false`.
+    pub progress_bar: bool,
+    /// Draw a badge in the corner of the image showing the community rating.
+    ///
+    /// Defaults to `false`.
+    pub rating_badge: bool,
+    /// Width and height of the resulting image in pixels, at most `1024`.
+    ///
+    /// Defaults to `512`.
+    pub size: u32,
+}
+
+impl Default for ImageTransform {
+    fn default() -> Self {
+        Self {
+            square: SquareMode::Crop,
+            blur_backdrop: false,
+            progress_bar: false,
+            rating_badge: false,
+            size: 512,
+        }
+    }
+}
+
+/// How a non-square image is turned into a square one.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Default)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "lowercase")]
+pub enum SquareMode {
+    /// Cut off the edges of the image.
+    #[default]
+    Crop,
+    /// Keep the entire image and fill the remaining space.
+    Pad,
+}
+
+impl ImageTransform {
+    /// Key that changes whenever the output of [`transform`] would,
+    /// used to avoid uploading the same image twice.
+    ///
+    /// The progress comes last so [`stale_progress`] can find the images it replaces.
+    pub(crate) fn cache_key(&self, progress: Option<f64>, rating: Option<f64>) -> String {
+        let mut key = format!("{:?}-{}-{}", self.square, self.blur_backdrop, self.size());
+
+        if self.rating_badge {
+            key += &format!("-r{}", rating.map(pips).unwrap_or_default());
+        }
+
+        if self.progress_bar {
+            // Only re-render every 10% so the image isn't uploaded on every update
+            let step = progress.map(|p| (p.clamp(0.0, 1.0) * 10.0) as u8);
+            key += &format!("{}{}", PROGRESS_KEY, step.unwrap_or_default());
+        }
+
+        key
+    }
+
+    fn size(&self) -> u32 {
+        self.size.clamp(1, MAX_IMAGE_SIZE)
+    }
+}
+
+/// Prefix of the cache ids of images that only differ from `id` in progress,
+/// they won't be shown again once playback moved on.
+///
+/// `None` if the images of `id` don't have a progress bar.
+pub(crate) fn stale_progress(id: &str, options: &ImageTransform) -> Option<String> {
+    if !options.progress_bar {
+        return None;
+    }
+
+    id.rsplit_once(PROGRESS_KEY)
+        .map(|(item, _)| format!("{}{}", item, PROGRESS_KEY))
+}
+
+/// Transforms the raw image bytes according to `options` and returns them as a PNG.
+///
+/// `progress` is a value between `0.0` and `1.0`, `rating` is a value between `0.0` and `10.0`.
+pub(crate) fn transform(
+    bytes: &[u8],
+    options: &ImageTransform,
+    progress: Option<f64>,
+    rating: Option<f64>,
+) -> JfResult<Vec<u8>> {
+    let source = image::load_from_memory(bytes)?;
+    let size = options.size();
+
+    let mut canvas = match options.square {
+        SquareMode::Crop => source
+            .resize_to_fill(size, size, FilterType::Lanczos3)
+            .to_rgba8(),
+        SquareMode::Pad => pad(&source, size, options.blur_backdrop),
+    };
+
+    if options.progress_bar {
+        if let Some(progress) = progress {
+            draw_progress_bar(&mut canvas, progress);
+        }
+    }
+
+    if options.rating_badge {
+        if let Some(rating) = rating {
+            draw_rating_badge(&mut canvas, rating);
+        }
+    }
+
+    let mut output = Cursor::new(Vec::new());
+    DynamicImage::ImageRgba8(canvas).write_to(&mut output, ImageFormat::Png)?;
+
+    Ok(output.into_inner())
+}
+
+fn pad(source: &DynamicImage, size: u32, blur_backdrop: bool) -> RgbaImage {
+    let mut canvas = if blur_backdrop {
+        // Blurring a small copy and scaling it up is a lot cheaper than blurring at full size
+        let small = (size / 8).max(1);
+        source
+            .resize_to_fill(small, small, FilterType::Triangle)
+            .blur(2.0)
+            .resize_exact(size, size, FilterType::Triangle)
+            .to_rgba8()
+    } else {
+        RgbaImage::from_pixel(size, size, Rgba([0, 0, 0, 255]))
+    };
+
+    let foreground = source.resize(size, size, FilterType::Lanczos3).to_rgba8();
+    let x = (size - foreground.width()) / 2;
+    let y = (size - foreground.height()) / 2;
+
+    imageops::overlay(&mut canvas, &foreground, x.into(), y.into());
+
+    canvas
+}
+
+fn draw_progress_bar(canvas: &mut RgbaImage, progress: f64) {
+    let (width, height) = canvas.dimensions();
+    // Tiny images are covered by the bar entirely
+    let bar_height = (height / 40).max(2).min(height);
+    let filled = (width as f64 * progress.clamp(0.0, 1.0)) as u32;
+    let y = height - bar_height;
+
+    fill_rect(canvas, 0, y, width, bar_height, SHADE_COLOR);
+    fill_rect(canvas, 0, y, filled, bar_height, PROGRESS_COLOR);
+}
+
+fn draw_rating_badge(canvas: &mut RgbaImage, rating: f64) {
+    let size = canvas.width();
+    let pip = (size / 32).max(2);
+    let gap = (pip / 2).max(1);
+    let padding = pip;
+    let badge_width = padding * 2 + pip * 5 + gap * 4;
+    let badge_height = padding * 2 + pip;
+    let x = size.saturating_sub(badge_width + padding);
+    let y = padding;
+
+    fill_rect(canvas, x, y, badge_width, badge_height, SHADE_COLOR);
+
+    let filled = pips(rating);
+    for i in 0..5 {
+        let color = if i < filled {
+            RATING_COLOR
+        } else {
+            INACTIVE_COLOR
+        };
+        fill_rect(
+            canvas,
+            x + padding + i * (pip + gap),
+            y + padding,
+            pip,
+            pip,
+            color,
+        );
+    }
+}
+
+/// Converts a rating out of 10 to a number of filled pips out of 5.
+fn pips(rating: f64) -> u32 {
+    (rating.clamp(0.0, 10.0) / 2.0).round() as u32
+}
+
+fn fill_rect(canvas: &mut RgbaImage, x: u32, y: u32, width: u32, height: u32, color: Rgba<u8>) {
+    let x_end = (x + width).min(canvas.width());
+    let y_end = (y + height).min(canvas.height());
+
+    for py in y..y_end {
+        for px in x..x_end {
+            canvas.get_pixel_mut(px, py).blend(&color);
+        }
+    }
+}
@@ -10,6 +10,8 @@ pub enum JfError {
     ContentBlacklist,
     MissingRequiredValues,
     NoImage,
+    /// User could not be found on the jellyfin server
+    UserNotFound,
 }
 
 impl Error for JfError {}
@@ -21,6 +23,7 @@ impl Display for JfError {
             JfError::UnrecognizedMediaType => write!(f, "unrecognized media type"),
             JfError::ContentBlacklist => write!(f, "content is blacklisted"),
             JfError::NoImage => write!(f, "media does not have an image"),
+            JfError::UserNotFound => write!(f, "user could not be found"),
         }
     }
 }
@@ -1,6 +1,6 @@
 use std::{
     fs::{self, File, OpenOptions},
-    io::{Error, ErrorKind, Write},
+    io::{Error, Write},
     path::Path,
 };
 
@@ -36,19 +36,23 @@ struct Data {
 
 pub fn get_image(client: &Client) -> JfResult<Url> {
     let mut image_urls = read_file(client)?;
+    let id = cache_id(client);
 
-    if let Some(image_url) = image_urls
-        .iter()
-        .find(|image_url| client.session.as_ref().unwrap().item_id == image_url.id)
-    {
+    if let Some(image_url) = image_urls.iter().find(|image_url| id == image_url.id) {
         Ok(Url::parse(&image_url.url)?)
     } else {
         let imgur_url = upload(client)?;
 
-        let image_url = ImageUrl::new(
-            &client.session.as_ref().unwrap().item_id,
-            imgur_url.as_str(),
-        );
+        #[cfg(feature = "transform")]
+        if let Some(stale) = client
+            .image_transform
+            .as_ref()
+            .and_then(|transform| crate::artwork::stale_progress(&id, transform))
+        {
+            image_urls.retain(|image_url| !image_url.id.starts_with(&stale));
+        }
+
+        let image_url = ImageUrl::new(id, imgur_url.as_str());
 
         image_urls.push(image_url);
 
@@ -65,6 +69,35 @@ pub fn get_image(client: &Client) -> JfResult<Url> {
     }
 }
 
+/// Id used to look up the image in the cache,
+/// transformed images get their own entries since they differ from the original.
+///
+/// The image tag is part of the id so changed artwork is uploaded again,
+/// and the name of the source since item ids are only unique on their own server.
+pub(crate) fn cache_id(client: &Client) -> String {
+    let session = client.session.as_ref().unwrap();
+    let source = client.sources[client.source].name();
+
+    let id = match &session.image_tag {
+        Some(tag) => format!("{}/{}-{}", source, session.item_id, tag),
+        None => format!("{}/{}", source, session.item_id),
+    };
+
+    #[cfg(feature = "transform")]
+    if let Some(transform) = &client.image_transform {
+        return format!(
+            "{}-{}",
+            id,
+            transform.cache_key(
+                session.progress(),
+                session.now_playing_item.community_rating
+            )
+        );
+    }
+
+    id
+}
+
 fn read_file(client: &Client) -> JfResult<Vec<ImageUrl>> {
     if let Ok(contents_raw) = fs::read_to_string(&client.imgur_options.urls_location) {
         if let Ok(contents) = serde_json::from_str::<Vec<ImageUrl>>(&contents_raw) {
@@ -74,10 +107,7 @@ fn read_file(client: &Client) -> JfResult<Vec<ImageUrl>> {
 
     let path = Path::new(&client.imgur_options.urls_location)
         .parent()
-        .ok_or(Error::new(
-            ErrorKind::Other,
-            "Can't find parent folder of urls.json",
-        ))?;
+        .ok_or(Error::other("Can't find parent folder of urls.json"))?;
 
     fs::create_dir_all(path)?;
 
@@ -93,7 +123,7 @@ fn read_file(client: &Client) -> JfResult<Vec<ImageUrl>> {
 }
 
 fn upload(client: &Client) -> JfResult<Url> {
-    let image_bytes = client.reqwest.get(client.get_image()?).send()?.bytes()?;
+    let image_bytes = client.get_image_bytes()?;
 
     let imgur_client = reqwest::blocking::Client::builder().build()?;
 
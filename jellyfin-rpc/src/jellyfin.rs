@@ -1,4 +1,5 @@
 use serde::{de::Visitor, Deserialize, Serialize};
+use std::collections::HashMap;
 use std::time::{SystemTime, SystemTimeError, UNIX_EPOCH};
 
 #[derive(Deserialize, Debug)]
@@ -11,31 +12,53 @@ pub struct RawSession {
 
 impl RawSession {
     pub fn build(self) -> Session {
-        //TODO: Figure out how to avoid this clone
-        let now_playing_item = self.now_playing_item.clone().unwrap();
-
-        let id = match now_playing_item.media_type {
-            MediaType::Episode => now_playing_item.series_id.unwrap_or(now_playing_item.id),
-            MediaType::Music => now_playing_item.album_id.unwrap_or(now_playing_item.id),
-            _ => now_playing_item.id,
-        };
-
-        Session {
-            now_playing_item: self.now_playing_item.unwrap(),
-            play_state: self.play_state.unwrap(),
-            item_id: id.to_string(),
-        }
+        Session::new(self.now_playing_item.unwrap(), self.play_state.unwrap())
     }
 }
 
+/// Something that is being played.
 #[derive(Debug)]
 pub struct Session {
     pub now_playing_item: NowPlayingItem,
     pub play_state: PlayState,
     pub item_id: String,
+    /// Tag of the primary image belonging to `item_id`, `None` if there is no image.
+    pub image_tag: Option<String>,
 }
 
 impl Session {
+    /// Creates a session for the item, the artwork is taken from the series or album when it has one.
+    pub fn new(now_playing_item: NowPlayingItem, play_state: PlayState) -> Self {
+        let own_image_tag = now_playing_item.own_image_tag();
+
+        // Episodes and songs use the artwork of their series/album when it exists,
+        // otherwise fall back to the item's own primary image
+        let (id, image_tag) = match now_playing_item.media_type {
+            MediaType::Episode => match (
+                &now_playing_item.series_id,
+                &now_playing_item.series_primary_image_tag,
+            ) {
+                (Some(series_id), Some(tag)) => (series_id, Some(tag.clone())),
+                _ => (&now_playing_item.id, own_image_tag),
+            },
+            MediaType::Music => match (
+                &now_playing_item.album_id,
+                &now_playing_item.album_primary_image_tag,
+            ) {
+                (Some(album_id), Some(tag)) => (album_id, Some(tag.clone())),
+                _ => (&now_playing_item.id, own_image_tag),
+            },
+            _ => (&now_playing_item.id, own_image_tag),
+        };
+
+        Session {
+            item_id: id.to_string(),
+            image_tag,
+            now_playing_item,
+            play_state,
+        }
+    }
+
     /// Formats artists with comma separation and a final "and" before the last name.
     pub fn format_artists(&self) -> String {
         // let default is to create a longer lived value for artists_vec
@@ -60,6 +83,14 @@ impl Session {
         artists
     }
 
+    /// How far into the media playback is, from `0.0` to `1.0`.
+    pub fn progress(&self) -> Option<f64> {
+        let position = self.play_state.position_ticks?;
+        let runtime = self.now_playing_item.run_time_ticks.filter(|r| *r > 0)?;
+
+        Some(position as f64 / runtime as f64)
+    }
+
     pub fn get_time(&self) -> Result<PlayTime, SystemTimeError> {
         match self.now_playing_item.media_type {
             MediaType::Book => return Ok(PlayTime::None),
@@ -93,15 +124,20 @@ impl Session {
     }
 }
 
+/// Where playback is, see `Session::get_time()`.
 #[derive(PartialEq)]
 pub enum PlayTime {
+    /// Started and ends at these unix timestamps.
     Some(i64, i64),
+    /// Playback is paused or the position isn't known.
     Paused,
+    /// The media has no length, like books and live tv.
     None,
 }
 
 /// Contains information about buttons displayed in Discord
 #[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub struct Button {
     /// What the name should be showed as in Discord.
     ///
@@ -110,6 +146,8 @@ pub struct Button {
     pub name: String,
     /// What clicking it should point to in Discord.
     ///
+    /// Set this to `"jellyfin"` to link to the item on the jellyfin web interface.
+    ///
     /// # Example
     /// `"https://example.com"`
     pub url: String,
@@ -143,9 +181,15 @@ impl Button {
     pub(crate) fn is_dynamic(&self) -> bool {
         self.name == "dynamic" && self.url == "dynamic"
     }
+
+    /// Whether the button links to the item on the jellyfin web interface.
+    pub(crate) fn is_jellyfin(&self) -> bool {
+        self.url == "jellyfin"
+    }
 }
 
-#[derive(Deserialize, Debug, Clone)]
+/// The item being played, as jellyfin describes it.
+#[derive(Deserialize, Debug, Clone, Default)]
 #[serde(rename_all = "PascalCase")]
 pub struct NowPlayingItem {
     // Generic
@@ -157,22 +201,48 @@ pub struct NowPlayingItem {
     pub production_year: Option<i64>,
     pub genres: Option<Vec<String>>,
     pub external_urls: Option<Vec<ExternalUrl>>,
+    pub provider_ids: Option<HashMap<String, String>>,
     pub critic_rating: Option<i64>,
     pub community_rating: Option<f64>,
     pub original_title: Option<String>,
     pub path: Option<String>,
+    pub parent_id: Option<String>,
+    pub tags: Option<Vec<String>>,
+    pub official_rating: Option<String>,
+    pub studios: Option<Vec<NameId>>,
+    pub image_tags: Option<HashMap<String, String>>,
+    pub primary_image_tag: Option<String>,
     // Episode related
     pub parent_index_number: Option<i32>,
     pub index_number: Option<i32>,
     pub index_number_end: Option<i32>,
     pub series_name: Option<String>,
     pub series_id: Option<String>,
+    pub season_id: Option<String>,
     pub series_studio: Option<String>,
+    pub series_primary_image_tag: Option<String>,
     // Audio related
     pub artists: Option<Vec<String>>,
     pub extra_type: Option<String>,
     pub album_id: Option<String>,
     pub album: Option<String>,
+    pub album_primary_image_tag: Option<String>,
+}
+
+impl NowPlayingItem {
+    /// Tag of the item's own primary image, if jellyfin has one.
+    fn own_image_tag(&self) -> Option<String> {
+        self.image_tags
+            .as_ref()
+            .and_then(|tags| tags.get("Primary").cloned())
+            .or_else(|| self.primary_image_tag.clone())
+    }
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(rename_all = "PascalCase")]
+pub struct NameId {
+    pub name: String,
 }
 
 #[derive(Deserialize, Debug, Clone)]
@@ -183,7 +253,7 @@ pub struct ExternalUrl {
 }
 
 /// The type of the currently playing content.
-#[derive(Debug, PartialEq, Clone, Copy)]
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
 pub enum MediaType {
     /// If the content playing is a Movie.
     Movie,
@@ -198,6 +268,7 @@ pub enum MediaType {
     /// If the content playing is an Audio Book.
     AudioBook,
     /// If the content is unrecognized.
+    #[default]
     None,
 }
 
@@ -258,6 +329,25 @@ impl<'de> Visitor<'de> for MediaTypeVisitor {
     }
 }
 
+/// Accepts the same names as deserializing does, in lowercase or as they're displayed.
+#[cfg(feature = "schema")]
+impl schemars::JsonSchema for MediaType {
+    fn schema_name() -> std::borrow::Cow<'static, str> {
+        "MediaType".into()
+    }
+
+    fn json_schema(_: &mut schemars::SchemaGenerator) -> schemars::Schema {
+        schemars::json_schema!({
+            "description": "The type of content, names are case-insensitive.",
+            "type": "string",
+            "enum": [
+                "movie", "episode", "music", "audio", "livetv", "tvchannel", "book", "audiobook",
+                "Movie", "Episode", "Music", "Audio", "LiveTv", "TvChannel", "Book", "AudioBook",
+            ]
+        })
+    }
+}
+
 impl std::fmt::Display for MediaType {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         let res = match self {
@@ -273,12 +363,6 @@ impl std::fmt::Display for MediaType {
     }
 }
 
-impl Default for MediaType {
-    fn default() -> Self {
-        Self::None
-    }
-}
-
 impl From<&'static str> for MediaType {
     fn from(value: &'static str) -> Self {
         match value {
@@ -307,7 +391,8 @@ impl From<String> for MediaType {
     }
 }
 
-#[derive(Deserialize, Debug)]
+/// Whether the item is paused and how far into it playback is.
+#[derive(Deserialize, Debug, Default)]
 #[serde(rename_all = "PascalCase")]
 pub struct PlayState {
     pub is_paused: bool,
@@ -319,4 +404,37 @@ pub struct PlayState {
 pub struct VirtualFolder {
     pub name: Option<String>,
     pub locations: Vec<String>,
+    pub item_id: Option<String>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "PascalCase")]
+pub struct PublicSystemInfo {
+    pub id: String,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "PascalCase")]
+pub struct User {
+    pub name: String,
+    pub id: String,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "PascalCase")]
+pub struct Items {
+    pub items: Vec<ItemTags>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "PascalCase")]
+pub struct ItemId {
+    pub id: String,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "PascalCase")]
+pub struct ItemTags {
+    pub id: String,
+    pub tags: Option<Vec<String>>,
 }
@@ -1,21 +1,37 @@
-use discord_rich_presence::activity::{ActivityType, Button as ActButton};
-use discord_rich_presence::{
-    activity::{Activity, Assets, Timestamps},
-    DiscordIpc, DiscordIpcClient,
-};
+#[cfg(feature = "transform")]
+pub use artwork::{ImageTransform, SquareMode};
+use discord_rich_presence::{DiscordIpc, DiscordIpcClient};
 pub use error::JfError;
-pub use jellyfin::{Button, MediaType};
-use jellyfin::{ExternalUrl, NowPlayingItem, PlayTime, RawSession, Session, VirtualFolder};
+use jellyfin::VirtualFolder;
+pub use jellyfin::{
+    Button, ExternalUrl, MediaType, NameId, NowPlayingItem, PlayState, PlayTime, Session,
+};
 use log::{debug, warn};
-use reqwest::header::{HeaderMap, AUTHORIZATION};
+use percent_encoding::{utf8_percent_encode, AsciiSet, NON_ALPHANUMERIC};
+use rules::Rule;
+pub use rules::{BlacklistAction, ContentRule};
+use schedule::CompiledSchedule;
+pub use schedule::{Schedule, ScheduledDisplay, TimeWindow};
 use serde::{Deserialize, Serialize};
+pub use server::{ServerBuilder, ServerKind};
+use sink::DiscordSink;
+pub use sink::{Presence, PresenceSink};
+pub use source::SessionSource;
+use std::collections::{BTreeMap, HashMap};
 use std::str::FromStr;
-use std::time::SystemTime;
+use std::time::{Duration, SystemTime};
 use url::Url;
 
+#[cfg(feature = "transform")]
+mod artwork;
 mod error;
 mod external;
 mod jellyfin;
+mod rules;
+mod schedule;
+mod server;
+mod sink;
+mod source;
 #[cfg(test)]
 mod tests;
 
@@ -23,21 +39,73 @@ pub(crate) type JfResult<T> = Result<T, Box<dyn std::error::Error>>;
 
 pub const VERSION: Option<&'static str> = option_env!("CARGO_PKG_VERSION");
 
+/// Characters that are escaped when a placeholder is put into a button url
+const URL_VALUE: &AsciiSet = &NON_ALPHANUMERIC
+    .remove(b'-')
+    .remove(b'_')
+    .remove(b'.')
+    .remove(b'~');
+
+/// Placeholders that can be used in `DisplayFormat`s.
+pub const DISPLAY_PLACEHOLDERS: &[&str] = &[
+    "{title}",
+    "{original-title}",
+    "{show-title}",
+    "{episode}",
+    "{episode-padded}",
+    "{season}",
+    "{season-padded}",
+    "{track}",
+    "{album}",
+    "{artists}",
+    "{genres}",
+    "{year}",
+    "{studio}",
+    "{critic-score}",
+    "{community-score}",
+    "{version}",
+    "{sep}",
+    "{__default}",
+];
+
+/// Placeholders that can be used in the name and url of a `Button`.
+pub const BUTTON_PLACEHOLDERS: &[&str] = &[
+    "{title}",
+    "{item-id}",
+    "{server-id}",
+    "{public-url}",
+    "{imdb-id}",
+    "{tmdb-id}",
+    "{musicbrainz-album-id}",
+    "{year}",
+    "{show-title}",
+    "{season}",
+    "{episode}",
+    "{track}",
+    "{album}",
+    "{artists}",
+];
+
 /// Client used to interact with jellyfin and discord
 pub struct Client {
-    discord_ipc_client: DiscordIpcClient,
-    url: Url,
-    usernames: Vec<String>,
-    reqwest: reqwest::blocking::Client,
+    /// `None` when discord is turned off
+    discord: Option<DiscordSink>,
+    sinks: Vec<Box<dyn PresenceSink>>,
+    sources: Vec<Box<dyn SessionSource>>,
+    /// Index of the source `session` is from
+    source: usize,
     session: Option<Session>,
     buttons: Option<Vec<Button>>,
+    dynamic_buttons: DynamicButtons,
     music_display_options: DisplayOptions,
     movies_display_options: DisplayOptions,
     episodes_display_options: DisplayOptions,
-    blacklist: Blacklist,
+    schedule: CompiledSchedule,
     show_paused: bool,
     show_images: bool,
     imgur_options: ImgurOptions,
+    #[cfg(feature = "transform")]
+    image_transform: Option<ImageTransform>,
     large_image_text: String,
 }
 
@@ -47,17 +115,76 @@ impl Client {
         ClientBuilder::new()
     }
 
-    /// Connects to the discord socket
+    /// Connects to the discord socket and every other sink
     pub fn connect(&mut self) -> JfResult<()> {
-        self.discord_ipc_client.connect()
+        self.for_each_sink(|sink| sink.connect())
     }
 
-    /// Reconnects to the discord socket
+    /// Reconnects to the discord socket and every other sink
     pub fn reconnect(&mut self) -> JfResult<()> {
-        self.discord_ipc_client.reconnect()
+        self.for_each_sink(|sink| sink.reconnect())
     }
 
-    /// Clears current activity on discord if anything is being displayed
+    /// Replaces every option of the client with the ones from `builder`,
+    /// the connection to discord is kept unless the application id changed.
+    /// Other sinks are replaced by the ones from `builder` and connected.
+    ///
+    /// If anything can't be connected the client is left as it was.
+    ///
+    /// # Example
+    /// ```no_run
+    /// use jellyfin_rpc::Client;
+    ///
+    /// let mut builder = Client::builder();
+    /// builder.api_key("abcd1234")
+    ///     .url("https://jellyfin.example.com")
+    ///     .username("user");
+    ///
+    /// let mut client = builder.build().unwrap();
+    /// client.connect().unwrap();
+    ///
+    /// let mut builder = Client::builder();
+    /// builder.api_key("abcd1234")
+    ///     .url("https://jellyfin.example.com")
+    ///     .username("user")
+    ///     .show_paused(false);
+    ///
+    /// client.update(builder).unwrap();
+    /// ```
+    pub fn update(&mut self, builder: ClientBuilder) -> JfResult<()> {
+        let mut client = builder.build()?;
+
+        let reconnect = match (&client.discord, &self.discord) {
+            (Some(new), Some(old)) => new.client.client_id != old.client.client_id,
+            _ => true,
+        };
+
+        // Everything new is connected first so a failure leaves the old client working
+        if reconnect {
+            debug!("Application id changed, connecting to discord again");
+            if let Some(new) = &mut client.discord {
+                new.connect()?;
+            }
+        }
+
+        for sink in &mut client.sinks {
+            sink.connect()?;
+        }
+
+        match (&mut client.discord, &mut self.discord) {
+            (Some(new), Some(old)) if !reconnect => std::mem::swap(new, old),
+            (_, Some(old)) => {
+                let _ = old.client.close();
+            }
+            _ => (),
+        }
+
+        *self = client;
+
+        Ok(())
+    }
+
+    /// Clears current activity on discord and every other sink if anything is being displayed
     ///
     /// # Example
     /// ```no_run
@@ -77,11 +204,75 @@ impl Client {
     /// client.clear_activity().unwrap();
     /// ```
     pub fn clear_activity(&mut self) -> JfResult<()> {
-        self.discord_ipc_client.clear_activity()
+        self.for_each_sink(|sink| sink.clear_presence())
+    }
+
+    /// Runs `f` on discord and every other sink, the first error is returned
+    /// after every sink has been tried so one broken sink doesn't block the others.
+    fn for_each_sink<F>(&mut self, mut f: F) -> JfResult<()>
+    where
+        F: FnMut(&mut dyn PresenceSink) -> JfResult<()>,
+    {
+        let mut result = Ok(());
+        let several = usize::from(self.discord.is_some()) + self.sinks.len() > 1;
+
+        let discord = self
+            .discord
+            .iter_mut()
+            .map(|discord| discord as &mut dyn PresenceSink);
+        let sinks = self.sinks.iter_mut().map(|sink| sink.as_mut());
+
+        for sink in discord.chain(sinks) {
+            if let Err(err) = f(sink) {
+                if several {
+                    warn!("{} failed: {}", sink.name(), err);
+                }
+                if result.is_ok() {
+                    result = Err(err);
+                }
+            }
+        }
+
+        result
+    }
+
+    /// Reloads the library list from every server and forgets the cached tags and ancestors of items.
+    ///
+    /// This happens on its own when the cache expires, see `ClientBuilder::blacklist_cache_ttl()`.
+    /// If it fails the library list stays unloaded until the next attempt,
+    /// the first error is returned after every source has been tried.
+    ///
+    /// # Example
+    /// ```no_run
+    /// use jellyfin_rpc::Client;
+    ///
+    /// let mut builder = Client::builder();
+    /// builder.api_key("abcd1234")
+    ///     .url("https://jellyfin.example.com")
+    ///     .username("user");
+    ///
+    /// let mut client = builder.build().unwrap();
+    ///
+    /// client.refresh_blacklist().unwrap();
+    /// ```
+    pub fn refresh_blacklist(&mut self) -> JfResult<()> {
+        let mut result = Ok(());
+
+        for source in &mut self.sources {
+            if let Err(err) = source.refresh() {
+                if result.is_ok() {
+                    result = Err(err);
+                }
+            }
+        }
+
+        result
     }
 
     /// Gathers information from jellyfin about what is being played and displays it according to the options supplied to the builder.
     ///
+    /// Returns an empty string when nothing should be displayed, like during quiet hours.
+    ///
     /// # Example
     /// ```no_run
     /// use jellyfin_rpc::Client;
@@ -98,23 +289,16 @@ impl Client {
     /// client.set_activity().unwrap();
     /// ```
     pub fn set_activity(&mut self) -> JfResult<String> {
+        if self.schedule.is_quiet() {
+            debug!("Inside quiet hours, not displaying anything");
+            return Ok(String::new());
+        }
+
         self.get_session()?;
 
-        // Make sure the blacklist cache is loaded/valid
-        match &self.blacklist.libraries {
-            BlacklistedLibraries::Uninitialized => {
-                self.reload_blacklist();
-            }
-            BlacklistedLibraries::Initialized(_, init_time) => {
-                if SystemTime::now()
-                    .duration_since(*init_time)
-                    .map(|passed| passed.as_secs() > 3600)
-                    .unwrap_or(false)
-                {
-                    debug!("reloading blacklist after cache expiration");
-                    self.reload_blacklist();
-                }
-            }
+        if let Some(session) = &self.session {
+            let links = self.uses_links();
+            self.sources[self.source].prepare(session, links);
         }
 
         if let Some(session) = &self.session {
@@ -122,15 +306,18 @@ impl Client {
                 return Err(Box::new(JfError::UnrecognizedMediaType));
             }
 
-            if self.check_blacklist()? {
-                return Err(Box::new(JfError::ContentBlacklist));
-            }
-
-            let mut activity = Activity::new();
+            let redaction = match self.sources[self.source].check_blacklist(session) {
+                Some(BlacklistAction::Hide) => return Err(Box::new(JfError::ContentBlacklist)),
+                redaction => redaction,
+            };
 
             let mut image_url = Url::from_str("https://i.imgur.com/oX6vcds.png")?;
 
-            if session.now_playing_item.media_type == MediaType::LiveTv {
+            if let Some(redaction) = &redaction {
+                if let Some(image) = redaction.image() {
+                    image_url = Url::from_str(image)?;
+                }
+            } else if session.now_playing_item.media_type == MediaType::LiveTv {
                 image_url = Url::from_str("https://i.imgur.com/XxdHOqm.png")?;
             } else if self.imgur_options.enabled && self.show_images {
                 if let Ok(imgur_url) = external::imgur::get_image(self) {
@@ -146,226 +333,377 @@ impl Client {
                 }
             }
 
-            let mut assets = Assets::new().large_image(image_url.as_str());
-
-            if !self.large_image_text.is_empty() {
-                assets = assets.large_text(&self.large_image_text);
-            }
-
-            let mut timestamps = Timestamps::new();
-
-            match session.get_time()? {
-                PlayTime::Some(start, end) => timestamps = timestamps.start(start).end(end),
-                PlayTime::None => (),
-                PlayTime::Paused if self.show_paused => {
-                    assets = assets
-                        .small_image("https://i.imgur.com/wlHSvYy.png")
-                        .small_text("Paused");
-                }
+            let (start, end) = match session.get_time()? {
+                PlayTime::Some(start, end) => (Some(start), Some(end)),
+                PlayTime::None => (None, None),
+                PlayTime::Paused if self.show_paused => (None, None),
                 PlayTime::Paused => return Ok(String::new()),
-            }
+            };
 
-            let buttons: Vec<Button>;
+            // The runtime alone could give away what is being played
+            let (start, end, progress) = match &redaction {
+                Some(_) => (None, None, None),
+                None => (start, end, session.progress()),
+            };
 
-            if let Some(b) = self.get_buttons() {
-                // This gets around the value being dropped immediately at the end of this if statement
-                buttons = b;
-                activity = activity.buttons(
-                    buttons
-                        .iter()
-                        .map(|b| ActButton::new(&b.name, &b.url))
-                        .collect(),
-                );
-            }
+            let buttons = match &redaction {
+                Some(_) => Vec::new(),
+                None => self.get_buttons().unwrap_or_default(),
+            };
 
-            let mut state = self.get_state();
+            let state = match &redaction {
+                Some(_) => String::new(),
+                None => self.get_state(),
+            };
 
-            if state.len() > 128 {
-                state = state.chars().take(128).collect();
-            } else if state.len() < 3 {
-                // Add three zero width joiners due to discord requiring a minimum length of 3 chars in statuses
-                state += "‎‎‎";
-            }
+            let details = match &redaction {
+                Some(redaction) => redaction.details(session.now_playing_item.media_type),
+                None => self.get_details(),
+            };
 
-            let mut details = self.get_details();
+            let mut image_text = match &redaction {
+                Some(_) => String::new(),
+                None => self.get_image_text(),
+            };
 
-            if details.len() > 128 {
-                details = details.chars().take(128).collect();
-            } else if details.len() < 3 {
-                // add three (3) zero width joiners
-                details += "‎‎‎";
+            if image_text.is_empty() {
+                image_text = self.large_image_text.clone();
             }
 
-            let mut image_text = self.get_image_text();
-
             if image_text.is_empty() {
                 image_text = format!("Jellyfin-RPC v{}", VERSION.unwrap_or("UNKNOWN"));
             }
 
-            if image_text.len() > 128 {
-                image_text = image_text.chars().take(128).collect();
-            } else if image_text.len() < 3 {
-                // add three zero width joiners
-                image_text += "‎‎‎";
-            }
-
-            assets = assets.large_text(image_text.as_str());
-
-            match session.now_playing_item.media_type {
-                MediaType::Book => (),
-                MediaType::Music | MediaType::AudioBook => {
-                    activity = activity.activity_type(ActivityType::Listening)
-                }
-                _ => activity = activity.activity_type(ActivityType::Watching),
-            }
-
-            activity = activity
-                .timestamps(timestamps)
-                .assets(assets)
-                .details(&details)
-                .state(&state);
+            // Discord doesn't accept more than 128 characters
+            let truncate = |text: String| text.chars().take(128).collect::<String>();
+
+            let presence = Presence {
+                details: truncate(details),
+                state: truncate(state),
+                image_text: truncate(image_text),
+                image_url: image_url.to_string(),
+                media_type: session.now_playing_item.media_type,
+                paused: session.play_state.is_paused,
+                start,
+                end,
+                progress,
+                buttons,
+            };
 
-            self.discord_ipc_client.set_activity(activity)?;
+            self.for_each_sink(|sink| sink.set_presence(&presence))?;
 
-            return Ok(format!("{} | {}", details, state));
+            return Ok(format!("{} | {}", presence.details, presence.state));
         }
         Ok(String::new())
     }
 
+    /// Polls every source at the same time and picks the session to display,
+    /// see `ServerBuilder::priority()`.
     fn get_session(&mut self) -> JfResult<()> {
-        let sessions: Vec<RawSession> = self
-            .reqwest
-            .get(self.url.join("Sessions")?)
-            .send()?
-            .json()?;
-
-        debug!("Found {} sessions", sessions.len());
-
-        for session in sessions {
-            debug!("Session username is {:?}", session.user_name);
-            if let Some(username) = session.user_name.as_ref() {
-                if self
-                    .usernames
-                    .iter()
-                    .all(|u| username.to_lowercase() != u.to_lowercase())
-                {
-                    continue;
-                }
+        let results: Vec<Result<Option<Session>, String>> = if self.sources.len() == 1 {
+            vec![self.sources[0].get_session().map_err(|err| err.to_string())]
+        } else {
+            std::thread::scope(|scope| {
+                let handles: Vec<_> = self
+                    .sources
+                    .iter_mut()
+                    .map(|source| {
+                        scope.spawn(move || source.get_session().map_err(|err| err.to_string()))
+                    })
+                    .collect();
 
-                if session.now_playing_item.is_none() {
-                    continue;
-                }
-                debug!("NowPlayingItem exists");
+                handles
+                    .into_iter()
+                    .map(|handle| handle.join().expect("Polling a source panicked"))
+                    .collect()
+            })
+        };
 
-                if session.play_state.is_none() {
-                    continue;
+        let mut sessions = Vec::new();
+        let mut errors = Vec::new();
+
+        for (index, result) in results.into_iter().enumerate() {
+            match result {
+                Ok(Some(session)) => sessions.push((index, session)),
+                Ok(None) => (),
+                Err(err) if self.sources.len() > 1 => {
+                    warn!(
+                        "Failed to get sessions from {}: {}",
+                        self.sources[index].name(),
+                        err
+                    );
+                    errors.push(err);
                 }
-                debug!("PlayState exists");
+                Err(err) => errors.push(err),
+            }
+        }
 
-                let session = session.build();
+        // Nothing can be shown if every source failed
+        if errors.len() == self.sources.len() {
+            return Err(errors.remove(0).into());
+        }
 
-                if session
-                    .now_playing_item
-                    .extra_type
-                    .as_ref()
-                    .is_some_and(|et| et == "ThemeSong")
-                {
-                    debug!("Session is playing a theme song, continuing loop");
-                    continue;
+        match self.pick_session(sessions) {
+            Some((index, session)) => {
+                if index != self.source {
+                    debug!("Displaying session from {}", self.sources[index].name());
                 }
-
+                self.source = index;
                 self.session = Some(session);
-                return Ok(());
             }
+            None => self.session = None,
         }
-        self.session = None;
+
         Ok(())
     }
 
+    /// Picks which of the sessions found by the sources is displayed, something that is playing
+    /// wins over something that is paused, then the highest priority and then the first source.
+    fn pick_session(&self, sessions: Vec<(usize, Session)>) -> Option<(usize, Session)> {
+        sessions.into_iter().min_by_key(|(index, session)| {
+            (
+                session.play_state.is_paused,
+                std::cmp::Reverse(self.sources[*index].priority()),
+                *index,
+            )
+        })
+    }
+
     fn get_buttons(&self) -> Option<Vec<Button>> {
         let session = self.session.as_ref()?;
 
-        let mut activity_buttons: Vec<Button> = Vec::new();
+        let mut dynamic_buttons = self.get_dynamic_buttons().into_iter();
 
-        if let (Some(ext_urls), Some(buttons)) = (
-            &session.now_playing_item.external_urls,
-            self.buttons.as_ref(),
-        ) {
-            let ext_urls: Vec<&ExternalUrl> = ext_urls
-                .iter()
-                .filter(|eu| {
-                    !eu.url.starts_with("http://localhost")
-                        && !eu.url.starts_with("https://localhost")
-                })
-                .collect();
-            let mut i = 0;
-            for button in buttons {
-                if activity_buttons.len() == 2 {
-                    break;
-                }
+        let Some(buttons) = self.configured_buttons() else {
+            session.now_playing_item.external_urls.as_ref()?;
+            return Some(dynamic_buttons.take(2).collect());
+        };
 
-                if button.is_dynamic() {
-                    if ext_urls.len() > i {
-                        activity_buttons.push(Button::new(
-                            ext_urls[i].name.clone(),
-                            ext_urls[i].url.clone(),
-                        ));
-                        i += 1;
-                    }
-                } else {
-                    activity_buttons.push(button.clone())
-                }
+        let mut activity_buttons: Vec<Button> = Vec::new();
+
+        for button in buttons {
+            if activity_buttons.len() == 2 {
+                break;
             }
-            return Some(activity_buttons);
-        } else if let Some(buttons) = self.buttons.as_ref() {
-            for button in buttons {
-                if activity_buttons.len() == 2 {
-                    break;
-                }
 
-                if !button.is_dynamic() {
-                    activity_buttons.push(button.clone())
+            if button.is_dynamic() {
+                if let Some(button) = dynamic_buttons.next() {
+                    activity_buttons.push(button);
                 }
+            } else if let Some(button) = self.parse_button(button) {
+                activity_buttons.push(button)
             }
-            return Some(activity_buttons);
-        } else if let Some(ext_urls) = &session.now_playing_item.external_urls {
-            let ext_urls: Vec<&ExternalUrl> = ext_urls
+        }
+
+        Some(activity_buttons)
+    }
+
+    /// Buttons made from the external urls jellyfin has for the current item.
+    ///
+    /// Providers listed in `ClientBuilder::dynamic_button_providers()` come first in the order they were given,
+    /// every other provider follows in the order jellyfin returned them.
+    fn get_dynamic_buttons(&self) -> Vec<Button> {
+        let Some(ext_urls) = self
+            .session
+            .as_ref()
+            .and_then(|session| session.now_playing_item.external_urls.as_ref())
+        else {
+            return Vec::new();
+        };
+
+        let mut ext_urls: Vec<&ExternalUrl> = ext_urls
+            .iter()
+            .filter(|eu| {
+                !eu.url.starts_with("http://localhost") && !eu.url.starts_with("https://localhost")
+            })
+            .collect();
+
+        // Stable sort, so providers that aren't in the list keep their original order
+        ext_urls.sort_by_key(|eu| {
+            self.dynamic_buttons
+                .providers
                 .iter()
-                .filter(|eu| {
-                    !eu.url.starts_with("http://localhost")
-                        && !eu.url.starts_with("https://localhost")
-                })
-                .collect();
-            for ext_url in ext_urls {
-                if activity_buttons.len() == 2 {
-                    break;
-                }
+                .position(|provider| provider.eq_ignore_ascii_case(&eu.name))
+                .unwrap_or(usize::MAX)
+        });
 
-                activity_buttons.push(Button::new(ext_url.name.clone(), ext_url.url.clone()))
+        ext_urls
+            .into_iter()
+            .map(|eu| {
+                let name = self
+                    .dynamic_buttons
+                    .labels
+                    .get(&eu.name.to_lowercase())
+                    .unwrap_or(&eu.name);
+
+                // Discord doesn't accept button labels longer than 32 characters
+                Button::new(name.chars().take(32).collect(), eu.url.clone())
+            })
+            .collect()
+    }
+
+    /// Whether a configured button links to the item on jellyfin, see `SessionSource::prepare()`.
+    fn uses_links(&self) -> bool {
+        self.configured_buttons().is_some_and(|buttons| {
+            buttons
+                .iter()
+                .any(|b| b.is_jellyfin() || b.url.contains("{server-id}"))
+        })
+    }
+
+    /// Buttons for the current media type, falls back to the buttons shared by every media type.
+    fn configured_buttons(&self) -> Option<&Vec<Button>> {
+        let session = self.session.as_ref()?;
+
+        let media_buttons = match session.now_playing_item.media_type {
+            MediaType::Music => self.music_display_options.buttons.as_ref(),
+            MediaType::Movie => self.movies_display_options.buttons.as_ref(),
+            MediaType::Episode => self.episodes_display_options.buttons.as_ref(),
+            _ => None,
+        };
+
+        media_buttons.or(self.buttons.as_ref())
+    }
+
+    /// Fills in the placeholders in a button's name and url.
+    ///
+    /// Returns `None` if the button uses a placeholder the current item has no value for.
+    fn parse_button(&self, button: &Button) -> Option<Button> {
+        if !button.is_jellyfin() && !button.name.contains('{') && !button.url.contains('{') {
+            return Some(button.clone());
+        }
+
+        let session = self.session.as_ref()?;
+        let source = &self.sources[self.source];
+        let mut name = button.name.clone();
+        let mut url = if button.is_jellyfin() {
+            source.link(session)?
+        } else {
+            button.url.clone()
+        };
+
+        for (placeholder, value) in source.button_placeholders(session) {
+            if !name.contains(placeholder) && !url.contains(placeholder) {
+                continue;
+            }
+
+            if value.is_empty() {
+                debug!(
+                    "Skipping button \"{}\", {} has no value",
+                    button.name, placeholder
+                );
+                return None;
             }
-            return Some(activity_buttons);
+
+            name = name.replace(placeholder, &value);
+            url = url.replace(placeholder, &value);
         }
-        None
+
+        for (placeholder, value) in self.button_placeholders() {
+            if !name.contains(placeholder) && !url.contains(placeholder) {
+                continue;
+            }
+
+            if value.is_empty() {
+                debug!(
+                    "Skipping button \"{}\", {} has no value",
+                    button.name, placeholder
+                );
+                return None;
+            }
+
+            name = name.replace(placeholder, &value);
+            url = url.replace(
+                placeholder,
+                &utf8_percent_encode(&value, URL_VALUE).to_string(),
+            );
+        }
+
+        if let Err(err) = Url::parse(&url) {
+            debug!("Skipping button \"{}\", invalid url: {}", button.name, err);
+            return None;
+        }
+
+        // Discord doesn't accept button labels longer than 32 characters
+        Some(Button::new(name.chars().take(32).collect(), url))
     }
 
-    fn get_image(&self) -> JfResult<Url> {
+    /// Values that can be used in button names and urls,
+    /// the ones that depend on the source come from `SessionSource::button_placeholders()`.
+    fn button_placeholders(&self) -> Vec<(&'static str, String)> {
         let session = self.session.as_ref().unwrap();
+        let item = &session.now_playing_item;
+
+        let provider_id = |provider: &str| {
+            item.provider_ids
+                .as_ref()
+                .and_then(|ids| ids.get(provider))
+                .cloned()
+                .unwrap_or_default()
+        };
 
-        let path = "Items/".to_string() + &session.item_id + "/Images/Primary";
+        let mut placeholders = vec![
+            ("{title}", item.name.clone()),
+            ("{item-id}", item.id.clone()),
+            ("{imdb-id}", provider_id("Imdb")),
+            ("{tmdb-id}", provider_id("Tmdb")),
+            ("{musicbrainz-album-id}", provider_id("MusicBrainzAlbum")),
+            (
+                "{year}",
+                item.production_year
+                    .map(|y| y.to_string())
+                    .unwrap_or_default(),
+            ),
+        ];
+
+        match item.media_type {
+            MediaType::Episode => placeholders.extend([
+                ("{show-title}", item.series_name.clone().unwrap_or_default()),
+                (
+                    "{season}",
+                    item.parent_index_number
+                        .map(|s| s.to_string())
+                        .unwrap_or_default(),
+                ),
+                (
+                    "{episode}",
+                    item.index_number.map(|e| e.to_string()).unwrap_or_default(),
+                ),
+            ]),
+            MediaType::Music => placeholders.extend([
+                ("{track}", item.name.clone()),
+                ("{album}", item.album.clone().unwrap_or_default()),
+                ("{artists}", session.format_artists()),
+            ]),
+            _ => (),
+        }
 
-        let image_url = self.url.join(&path)?;
+        placeholders
+    }
 
-        if self
-            .reqwest
-            .get(image_url.as_ref())
-            .send()?
-            .text()?
-            .contains("does not have an image of type Primary")
-        {
-            Err(Box::new(JfError::NoImage))
-        } else {
-            Ok(image_url)
+    fn get_image(&self) -> JfResult<Url> {
+        let session = self.session.as_ref().unwrap();
+
+        self.sources[self.source].image_url(session)
+    }
+
+    /// Downloads the image for the current session,
+    /// running it through the image pipeline if one is configured.
+    fn get_image_bytes(&self) -> JfResult<Vec<u8>> {
+        let session = self.session.as_ref().unwrap();
+        let bytes = self.sources[self.source].image(session)?;
+
+        #[cfg(feature = "transform")]
+        if let Some(transform) = &self.image_transform {
+            return artwork::transform(
+                &bytes,
+                transform,
+                session.progress(),
+                session.now_playing_item.community_rating,
+            );
         }
+
+        Ok(bytes)
     }
 
     fn sanitize_display_format(input: &str) -> String {
@@ -446,7 +784,7 @@ impl Client {
             .as_ref()
             .unwrap_or(&"".to_string())
             .clone();
-        let genres = &session
+        let genres = session
             .now_playing_item
             .genres
             .as_ref()
@@ -551,17 +889,33 @@ impl Client {
         Self::sanitize_display_format(&result).replace("{sep}", separator)
     }
 
+    /// Display format for `media_type`, fields set in a scheduled format that is
+    /// currently active replace the configured ones.
+    fn display_format(&self, media_type: MediaType) -> DisplayFormat {
+        let mut display = match media_type {
+            MediaType::Music => self.music_display_options.display.clone(),
+            MediaType::Movie => self.movies_display_options.display.clone(),
+            _ => self.episodes_display_options.display.clone(),
+        };
+
+        if let Some(scheduled) = self.schedule.display(media_type) {
+            display.details_text = scheduled.details_text.clone().or(display.details_text);
+            display.state_text = scheduled.state_text.clone().or(display.state_text);
+            display.image_text = scheduled.image_text.clone().or(display.image_text);
+        }
+
+        display
+    }
+
     fn get_details(&self) -> String {
         let session = self.session.as_ref().unwrap();
 
         match session.now_playing_item.media_type {
             MediaType::Music => {
-                let display_details_format = &self
-                    .music_display_options
-                    .display
+                let display_details_format = self
+                    .display_format(MediaType::Music)
                     .details_text
-                    .as_ref()
-                    .unwrap();
+                    .unwrap_or_default();
                 self.parse_music_display(
                     display_details_format
                         .replace("{__default}", "{track}")
@@ -569,12 +923,10 @@ impl Client {
                 )
             }
             MediaType::Movie => {
-                let display_details_format = &self
-                    .movies_display_options
-                    .display
+                let display_details_format = self
+                    .display_format(MediaType::Movie)
                     .details_text
-                    .as_ref()
-                    .unwrap();
+                    .unwrap_or_default();
                 self.parse_movies_display(
                     display_details_format
                         .replace("{__default}", "{title}")
@@ -582,12 +934,10 @@ impl Client {
                 )
             }
             MediaType::Episode => {
-                let display_details_format = &self
-                    .episodes_display_options
-                    .display
+                let display_details_format = self
+                    .display_format(MediaType::Episode)
                     .details_text
-                    .as_ref()
-                    .unwrap();
+                    .unwrap_or_default();
                 self.parse_episodes_display(
                     display_details_format
                         .replace("{__default}", "{show-title}")
@@ -609,22 +959,20 @@ impl Client {
 
         match session.now_playing_item.media_type {
             MediaType::Episode => {
-                let display_state_format = &self
-                    .episodes_display_options
-                    .display
+                let display_state_format = self
+                    .display_format(MediaType::Episode)
                     .state_text
-                    .as_ref()
-                    .unwrap();
-                self.parse_episodes_display(display_state_format.replace("{__default}", "").as_str())
+                    .unwrap_or_default();
+                self.parse_episodes_display(
+                    display_state_format.replace("{__default}", "").as_str(),
+                )
             }
             MediaType::LiveTv => "Live TV".to_string(),
             MediaType::Music => {
-                let display_state_format = &self
-                    .music_display_options
-                    .display
+                let display_state_format = self
+                    .display_format(MediaType::Music)
                     .state_text
-                    .as_ref()
-                    .unwrap();
+                    .unwrap_or_default();
                 self.parse_music_display(
                     display_state_format
                         .replace("{__default}", "By {artists} {sep} ")
@@ -669,12 +1017,10 @@ impl Client {
                 state
             }
             MediaType::Movie => {
-                let display_state_format = &self
-                    .movies_display_options
-                    .display
+                let display_state_format = self
+                    .display_format(MediaType::Movie)
                     .state_text
-                    .as_ref()
-                    .unwrap();
+                    .unwrap_or_default();
                 self.parse_movies_display(display_state_format.replace("{__default}", "").as_str())
             }
             _ => session
@@ -691,83 +1037,29 @@ impl Client {
 
         match session.now_playing_item.media_type {
             MediaType::Music => {
-                let display_image_format = &self
-                    .music_display_options
-                    .display
+                let display_image_format = self
+                    .display_format(MediaType::Music)
                     .image_text
-                    .as_ref()
-                    .unwrap();
-                self.parse_music_display(display_image_format)
+                    .unwrap_or_default();
+                self.parse_music_display(&display_image_format)
             }
             MediaType::Movie => {
-                let display_image_format = &self
-                    .movies_display_options
-                    .display
+                let display_image_format = self
+                    .display_format(MediaType::Movie)
                     .image_text
-                    .as_ref()
-                    .unwrap();
-                self.parse_movies_display(display_image_format)
+                    .unwrap_or_default();
+                self.parse_movies_display(&display_image_format)
             }
             MediaType::Episode => {
-                let display_image_format = &self
-                    .episodes_display_options
-                    .display
+                let display_image_format = self
+                    .display_format(MediaType::Episode)
                     .image_text
-                    .as_ref()
-                    .unwrap();
-                self.parse_episodes_display(display_image_format)
+                    .unwrap_or_default();
+                self.parse_episodes_display(&display_image_format)
             }
             _ => "".to_string(),
         }
     }
-
-    fn check_blacklist(&self) -> JfResult<bool> {
-        let session = self.session.as_ref().unwrap();
-
-        if self
-            .blacklist
-            .media_types
-            .iter()
-            .any(|m| m == &session.now_playing_item.media_type)
-        {
-            return Ok(true);
-        }
-
-        if self.blacklist.check_item(&session.now_playing_item) {
-            return Ok(true);
-        }
-
-        Ok(false)
-    }
-
-    /// Fetch the virtual folder list and filter out the blacklisted libraries
-    fn fetch_blacklist(&self) -> JfResult<Vec<VirtualFolder>> {
-        let virtual_folders: Vec<VirtualFolder> = self
-            .reqwest
-            .get(self.url.join("Library/VirtualFolders")?)
-            .send()?
-            .json()?;
-
-        Ok(virtual_folders
-            .into_iter()
-            .filter(|library_folder| {
-                self.blacklist
-                    .libraries_names
-                    .contains(library_folder.name.as_ref().unwrap_or(&String::new()))
-            })
-            .collect())
-    }
-
-    /// Reload the library list from Jellyfin and filter out the user-provided blacklisted libraries
-    fn reload_blacklist(&mut self) {
-        self.blacklist.libraries = match self.fetch_blacklist() {
-            Ok(blacklist) => BlacklistedLibraries::Initialized(blacklist, SystemTime::now()),
-            Err(err) => {
-                warn!("Failed to intialize blacklist: {}", err);
-                BlacklistedLibraries::Uninitialized
-            }
-        }
-    }
 }
 
 pub struct EpisodeDisplayOptions {
@@ -779,10 +1071,12 @@ pub struct EpisodeDisplayOptions {
 struct DisplayOptions {
     separator: String,
     display: DisplayFormat,
+    buttons: Option<Vec<Button>>,
 }
 
 /// Represents the formatting details for `Display`.
 #[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Default)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub struct DisplayFormat {
     /// First line of the activity.
     pub details_text: Option<String>,
@@ -860,7 +1154,24 @@ impl From<EpisodeDisplayOptions> for DisplayFormat {
 struct Blacklist {
     media_types: Vec<MediaType>,
     libraries_names: Vec<String>,
+    /// Only these media types are shown if it isn't empty
+    whitelist_media_types: Vec<MediaType>,
+    /// Only these libraries are shown if it isn't empty
+    whitelist_libraries_names: Vec<String>,
+    rules: Vec<Rule>,
+    /// What to do with blacklisted content, rules can override this
+    action: BlacklistAction,
+    /// Content is hidden if it, or its season, series or album has one of these tags
+    private_tags: Vec<String>,
+    /// Tags of items and their parents, keyed by item id
+    item_tags: HashMap<String, Vec<String>>,
+    /// Ids of the folders an item is in, keyed by item id
+    item_ancestors: HashMap<String, Vec<String>>,
     libraries: BlacklistedLibraries,
+    /// How long the library list is used before it's reloaded
+    cache_ttl: Duration,
+    /// Hide everything while the library list or the tags of an item can't be loaded
+    fail_closed: bool,
 }
 
 enum BlacklistedLibraries {
@@ -869,27 +1180,211 @@ enum BlacklistedLibraries {
 }
 
 impl Blacklist {
-    /// Check whether a [NowPlayingItem] is in a blacklisted library
+    /// Check whether the item is blacklisted and what should be done with it,
+    /// the most restrictive action wins when several things match.
+    fn check(&self, item: &NowPlayingItem) -> Option<BlacklistAction> {
+        if self.fail_closed && self.needs_libraries() {
+            if let BlacklistedLibraries::Uninitialized = self.libraries {
+                debug!("Library list isn't loaded, hiding everything");
+                return Some(BlacklistAction::Hide);
+            }
+        }
+
+        // Fetching the tags failed, so a private tag could be missed
+        if self.fail_closed
+            && self.needs_tags()
+            && Self::tagged_ids(item)
+                .into_iter()
+                .any(|id| !self.item_tags.contains_key(id))
+        {
+            debug!("Tags of the item aren't loaded, hiding it");
+            return Some(BlacklistAction::Hide);
+        }
+
+        let blacklisted_type = self.media_types.contains(&item.media_type);
+
+        let not_whitelisted_type = !self.whitelist_media_types.is_empty()
+            && !self.whitelist_media_types.contains(&item.media_type);
+
+        let tags = self.tags(item);
+
+        let private = self.check_private(&tags);
+        if private {
+            debug!("Item or one of its parents has a private tag");
+        }
+
+        let blacklisted =
+            blacklisted_type || not_whitelisted_type || self.check_item(item) || private;
+
+        let rule_actions = self
+            .rules
+            .iter()
+            .filter(|rule| rule.matches(item, &tags))
+            .map(|rule| {
+                debug!("Item matches a blacklist rule");
+                rule.action.as_ref().unwrap_or(&self.action)
+            });
+
+        blacklisted
+            .then_some(&self.action)
+            .into_iter()
+            .chain(rule_actions)
+            .max_by_key(|action| action.restrictiveness())
+            .cloned()
+    }
+
+    /// Whether any libraries are blacklisted or whitelisted
+    fn needs_libraries(&self) -> bool {
+        !self.libraries_names.is_empty() || !self.whitelist_libraries_names.is_empty()
+    }
+
+    /// Ids of the item and every parent whose tags should be checked
+    fn tagged_ids(playing_item: &NowPlayingItem) -> Vec<&String> {
+        [
+            Some(&playing_item.id),
+            playing_item.season_id.as_ref(),
+            playing_item.series_id.as_ref(),
+            playing_item.album_id.as_ref(),
+        ]
+        .into_iter()
+        .flatten()
+        .collect()
+    }
+
+    /// Whether the tags of the item have to be fetched, for private tags or rules
+    fn needs_tags(&self) -> bool {
+        !self.private_tags.is_empty() || self.rules.iter().any(Rule::uses_tags)
+    }
+
+    /// Tags of the item and its parents that have been fetched,
+    /// along with the ones in the session in case jellyfin ever includes them there
+    fn tags(&self, playing_item: &NowPlayingItem) -> Vec<String> {
+        Self::tagged_ids(playing_item)
+            .into_iter()
+            .filter_map(|id| self.item_tags.get(id))
+            .chain(playing_item.tags.as_ref())
+            .flatten()
+            .cloned()
+            .collect()
+    }
+
+    /// Check whether one of the tags of the item or its parents is private
+    fn check_private(&self, tags: &[String]) -> bool {
+        tags.iter().any(|tag| {
+            self.private_tags
+                .iter()
+                .any(|private| private.eq_ignore_ascii_case(tag))
+        })
+    }
+
+    /// Check whether a [NowPlayingItem] is in a blacklisted library or outside of the whitelisted ones
     fn check_item(&self, playing_item: &NowPlayingItem) -> bool {
         debug!("Checking if an item is blacklisted: {}", playing_item.name);
-        self.check_path(playing_item.path.as_ref().unwrap_or(&String::new()))
-    }
-
-    /// Check whether a path is in a blacklisted library
-    fn check_path(&self, item_path: &str) -> bool {
-        match &self.libraries {
-            BlacklistedLibraries::Initialized(libraries, _) => {
-                debug!("Checking path: {}", item_path);
-                libraries.iter().any(|blacklisted_mf| {
-                    blacklisted_mf.locations.iter().any(|physical_folder| {
-                        debug!("BL path: {}", physical_folder);
-                        item_path.starts_with(physical_folder)
-                    })
-                })
+
+        if self.check_library(playing_item, &self.libraries_names) == Some(true) {
+            return true;
+        }
+
+        if !self.whitelist_libraries_names.is_empty() {
+            debug!("Checking if an item is whitelisted: {}", playing_item.name);
+            return self.check_library(playing_item, &self.whitelist_libraries_names)
+                == Some(false);
+        }
+
+        false
+    }
+
+    /// Check whether an item is in one of the named libraries,
+    /// returns `None` if the library list hasn't been loaded
+    fn check_library(
+        &self,
+        playing_item: &NowPlayingItem,
+        library_names: &[String],
+    ) -> Option<bool> {
+        let BlacklistedLibraries::Initialized(libraries, _) = &self.libraries else {
+            return None;
+        };
+
+        let libraries: Vec<&VirtualFolder> = libraries
+            .iter()
+            .filter(|mf| library_names.contains(mf.name.as_ref().unwrap_or(&String::new())))
+            .collect();
+
+        let ancestors = self.item_ancestors.get(&playing_item.id);
+
+        let parent_ids: Vec<&String> = ancestors
+            .into_iter()
+            .flatten()
+            .chain(playing_item.parent_id.as_ref())
+            .collect();
+
+        let item_path = playing_item.path.as_deref().unwrap_or_default();
+        debug!("Checking path: {}", item_path);
+
+        Some(libraries.iter().any(|mf| {
+            if let Some(id) = &mf.item_id {
+                if parent_ids.contains(&id) {
+                    return true;
+                }
+
+                // The ancestors include the library itself, so the path isn't needed
+                if ancestors.is_some() {
+                    return false;
+                }
             }
-            BlacklistedLibraries::Uninitialized => false,
+
+            mf.locations.iter().any(|physical_folder| {
+                debug!("Library path: {}", physical_folder);
+                path_in_folder(item_path, physical_folder)
+            })
+        }))
+    }
+}
+
+/// Whether `path` is inside `folder`, only whole path components are compared
+/// so `/media/Anime Movies` isn't inside `/media/Anime`.
+///
+/// Windows and UNC paths are compared without caring about the kind of slash or casing.
+fn path_in_folder(path: &str, folder: &str) -> bool {
+    let windows = |p: &str| p.contains('\\') || p.as_bytes().get(1) == Some(&b':');
+
+    let normalize = |p: &str| {
+        let mut p = p.replace('\\', "/");
+
+        if windows(path) || windows(folder) {
+            p = p.to_lowercase();
         }
+
+        p.trim_end_matches('/').to_string()
+    };
+
+    let path = normalize(path);
+    let folder = normalize(folder);
+
+    if path.is_empty() || folder.is_empty() {
+        return false;
     }
+
+    path == folder
+        || path
+            .strip_prefix(&folder)
+            .is_some_and(|rest| rest.starts_with('/'))
+}
+
+#[derive(Default)]
+struct JellyfinButton {
+    access_user: Option<String>,
+    access_user_id: Option<String>,
+    /// Whether `access_user` can see an item and when that was checked, keyed by item id.
+    /// Entries expire together with the library list, see `ClientBuilder::blacklist_cache_ttl()`.
+    accessible: HashMap<String, (bool, SystemTime)>,
+}
+
+#[derive(Default)]
+struct DynamicButtons {
+    providers: Vec<String>,
+    /// Keyed by the lowercase provider name
+    labels: HashMap<String, String>,
 }
 
 struct ImgurOptions {
@@ -899,50 +1394,83 @@ struct ImgurOptions {
 }
 
 /// Used to build a new Client
-#[derive(Default)]
 pub struct ClientBuilder {
-    url: String,
+    server: ServerBuilder,
+    servers: Vec<ServerBuilder>,
+    sources: Vec<Box<dyn SessionSource>>,
+    discord: bool,
+    sinks: Vec<Box<dyn PresenceSink>>,
     client_id: String,
-    api_key: String,
-    self_signed: bool,
-    usernames: Vec<String>,
     buttons: Option<Vec<Button>>,
+    dynamic_button_providers: Vec<String>,
+    dynamic_button_labels: HashMap<String, String>,
     episode_divider: bool,
     episode_prefix: bool,
     episode_simple: bool,
     music_separator: String,
     music_display: DisplayFormat,
+    music_buttons: Option<Vec<Button>>,
     movies_separator: String,
     movies_display: DisplayFormat,
+    movies_buttons: Option<Vec<Button>>,
     episodes_separator: String,
     episodes_display: DisplayFormat,
-    blacklist_media_types: Vec<MediaType>,
-    blacklist_libraries: Vec<String>,
+    episodes_buttons: Option<Vec<Button>>,
+    schedule: Schedule,
     show_paused: bool,
     show_images: bool,
     use_imgur: bool,
     imgur_client_id: String,
     imgur_urls_file_location: String,
+    #[cfg(feature = "transform")]
+    image_transform: Option<ImageTransform>,
     large_image_text: String,
 }
 
+impl Default for ClientBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl ClientBuilder {
     /// Returns a ClientBuilder with some default options set
     pub fn new() -> Self {
         Self {
+            server: ServerBuilder::new(),
+            servers: Vec::new(),
+            sources: Vec::new(),
+            discord: true,
+            sinks: Vec::new(),
             client_id: "1053747938519679018".to_string(),
+            buttons: None,
+            dynamic_button_providers: Vec::new(),
+            dynamic_button_labels: HashMap::new(),
+            episode_divider: false,
+            episode_prefix: false,
+            episode_simple: false,
             music_separator: "-".to_string(),
             music_display: DisplayFormat::from(vec!["genres".to_string()]),
+            music_buttons: None,
             movies_separator: "-".to_string(),
             movies_display: DisplayFormat::from(vec!["genres".to_string()]),
+            movies_buttons: None,
             episodes_separator: "-".to_string(),
             episodes_display: DisplayFormat::from(EpisodeDisplayOptions {
                 divider: true,
                 prefix: true,
                 simple: false,
             }),
+            episodes_buttons: None,
+            schedule: Schedule::default(),
             show_paused: true,
-            ..Default::default()
+            show_images: false,
+            use_imgur: false,
+            imgur_client_id: String::new(),
+            imgur_urls_file_location: String::new(),
+            #[cfg(feature = "transform")]
+            image_transform: None,
+            large_image_text: String::new(),
         }
     }
 
@@ -950,7 +1478,16 @@ impl ClientBuilder {
     ///
     /// Has no default.
     pub fn url<T: Into<String>>(&mut self, url: T) -> &mut Self {
-        self.url = url.into();
+        self.server.url = url.into();
+        self
+    }
+
+    /// Jellyfin URL that is reachable by the people viewing your activity,
+    /// used for the `{public-url}` placeholder in buttons.
+    ///
+    /// Defaults to the value set in `ClientBuilder::url()`.
+    pub fn public_url<T: Into<String>>(&mut self, url: T) -> &mut Self {
+        self.server.public_url = Some(url.into());
         self
     }
 
@@ -966,7 +1503,7 @@ impl ClientBuilder {
     ///
     /// Has no default.
     pub fn api_key<T: Into<String>>(&mut self, api_key: T) -> &mut Self {
-        self.api_key = api_key.into();
+        self.server.api_key = api_key.into();
         self
     }
 
@@ -974,7 +1511,7 @@ impl ClientBuilder {
     ///
     /// Defaults to `false`.
     pub fn self_signed(&mut self, self_signed: bool) -> &mut Self {
-        self.self_signed = self_signed;
+        self.server.self_signed = self_signed;
         self
     }
 
@@ -986,7 +1523,7 @@ impl ClientBuilder {
     /// This overwrites the value set in `ClientBuilder::Username()`,
     /// only one of these 2 should be used
     pub fn usernames(&mut self, usernames: Vec<String>) -> &mut Self {
-        self.usernames = usernames;
+        self.server.usernames = usernames;
         self
     }
 
@@ -998,19 +1535,61 @@ impl ClientBuilder {
     /// This overwrites the value set in `ClientBuilder::Usernames()`,
     /// only one of these 2 should be used
     pub fn username<T: Into<String>>(&mut self, username: T) -> &mut Self {
-        self.usernames = vec![username.into()];
+        self.server.usernames = vec![username.into()];
         self
     }
 
     /// buttons to be displayed on the activity.
     /// Pass an empty `Vec::new()` to display no buttons
     ///
+    /// The name and url can contain placeholders that are filled in from the item being played:
+    /// `{title}`, `{year}`, `{item-id}`, `{server-id}`, `{imdb-id}`, `{tmdb-id}`, `{musicbrainz-album-id}` and `{public-url}`.
+    /// Episodes also have `{show-title}`, `{season}` and `{episode}`,
+    /// music has `{track}`, `{album}` and `{artists}`.
+    /// Buttons using a placeholder that the item has no value for are skipped.
+    ///
+    /// A button with `"jellyfin"` as its url links to the item on the jellyfin web interface.
+    ///
     /// Defaults to dynamic buttons generated from the Jellyfin session.
     pub fn buttons(&mut self, buttons: Vec<Button>) -> &mut Self {
         self.buttons = Some(buttons);
         self
     }
 
+    /// Which providers `"dynamic"` buttons should prefer, in order of preference.
+    /// The names are matched against the external urls jellyfin lists for the item,
+    /// for example `"IMDb"`, `"TheMovieDb"`, `"Trakt"` or `"MusicBrainz"`.
+    ///
+    /// Providers that aren't listed are still used if there are too few of the preferred ones.
+    ///
+    /// Defaults to `Vec::new()`, which uses the order jellyfin returns them in.
+    pub fn dynamic_button_providers(&mut self, providers: Vec<String>) -> &mut Self {
+        self.dynamic_button_providers = providers;
+        self
+    }
+
+    /// Custom labels for `"dynamic"` buttons, keyed by provider name.
+    /// Provider names are case-insensitive and labels are cut off after 32 characters.
+    ///
+    /// # Example
+    /// `{"IMDb": "View on IMDb"}`
+    ///
+    /// Defaults to `HashMap::new()`, which uses the provider name as the label.
+    pub fn dynamic_button_labels(&mut self, labels: HashMap<String, String>) -> &mut Self {
+        self.dynamic_button_labels = labels;
+        self
+    }
+
+    /// Only show buttons linking to the item on jellyfin (`"url": "jellyfin"`)
+    /// if this jellyfin user can access the item,
+    /// useful with a guest account shared with the people seeing your activity.
+    ///
+    /// Defaults to `None`, which always shows the button.
+    pub fn jellyfin_button_user<T: Into<String>>(&mut self, username: T) -> &mut Self {
+        self.server.jellyfin_button_user = Some(username.into());
+        self
+    }
+
     /// Splits season and episode numbers with a dash.
     ///
     /// Defaults to `false`.
@@ -1054,6 +1633,14 @@ impl ClientBuilder {
         self
     }
 
+    /// Buttons used instead of `ClientBuilder::buttons()` when the content is of this type.
+    ///
+    /// Defaults to `None`.
+    pub fn music_buttons(&mut self, buttons: Vec<Button>) -> &mut Self {
+        self.music_buttons = Some(buttons);
+        self
+    }
+
     pub fn movies_separator<T: Into<String>>(&mut self, separator: T) -> &mut Self {
         self.movies_separator = separator.into();
         self
@@ -1064,6 +1651,14 @@ impl ClientBuilder {
         self
     }
 
+    /// Buttons used instead of `ClientBuilder::buttons()` when the content is of this type.
+    ///
+    /// Defaults to `None`.
+    pub fn movies_buttons(&mut self, buttons: Vec<Button>) -> &mut Self {
+        self.movies_buttons = Some(buttons);
+        self
+    }
+
     pub fn episodes_separator<T: Into<String>>(&mut self, separator: T) -> &mut Self {
         self.episodes_separator = separator.into();
         self
@@ -1074,11 +1669,19 @@ impl ClientBuilder {
         self
     }
 
+    /// Buttons used instead of `ClientBuilder::buttons()` when the content is of this type.
+    ///
+    /// Defaults to `None`.
+    pub fn episodes_buttons(&mut self, buttons: Vec<Button>) -> &mut Self {
+        self.episodes_buttons = Some(buttons);
+        self
+    }
+
     /// Blacklist certain `MediaType`s so they don't display.
     ///
     /// Defaults to `Vec::new()`.
     pub fn blacklist_media_types(&mut self, media_types: Vec<MediaType>) -> &mut Self {
-        self.blacklist_media_types = media_types;
+        self.server.blacklist_media_types = media_types;
         self
     }
 
@@ -1086,7 +1689,135 @@ impl ClientBuilder {
     ///
     /// Defaults to `Vec::new()`.
     pub fn blacklist_libraries(&mut self, libraries: Vec<String>) -> &mut Self {
-        self.blacklist_libraries = libraries;
+        self.server.blacklist_libraries = libraries;
+        self
+    }
+
+    /// Hide content matching any of these rules,
+    /// see [ContentRule] for what can be matched against.
+    ///
+    /// Defaults to `Vec::new()`.
+    pub fn blacklist_rules(&mut self, rules: Vec<ContentRule>) -> &mut Self {
+        self.server.blacklist_rules = rules;
+        self
+    }
+
+    /// Hide content if it, or its season, series or album has one of these jellyfin tags.
+    /// Lets you hide specific shows or albums without moving them to a separate library.
+    ///
+    /// Defaults to `Vec::new()`.
+    pub fn private_tags(&mut self, tags: Vec<String>) -> &mut Self {
+        self.server.private_tags = tags;
+        self
+    }
+
+    /// What to do with blacklisted content, a [ContentRule] can override this with its own action.
+    ///
+    /// Defaults to `BlacklistAction::Hide`.
+    pub fn blacklist_action(&mut self, action: BlacklistAction) -> &mut Self {
+        self.server.blacklist_action = action;
+        self
+    }
+
+    /// How many seconds the library list is kept before it's loaded from jellyfin again.
+    ///
+    /// Defaults to `3600`.
+    pub fn blacklist_cache_ttl(&mut self, seconds: u64) -> &mut Self {
+        self.server.blacklist_cache_ttl = seconds;
+        self
+    }
+
+    /// Hide everything while the library list can't be loaded from jellyfin,
+    /// otherwise content from blacklisted libraries is shown until it loads.
+    /// Items whose tags can't be loaded are hidden as well.
+    ///
+    /// Only has an effect when libraries are blacklisted or whitelisted,
+    /// or when there are private tags or rules using tags.
+    ///
+    /// Defaults to `false`.
+    pub fn blacklist_fail_closed(&mut self, val: bool) -> &mut Self {
+        self.server.blacklist_fail_closed = val;
+        self
+    }
+
+    /// Hide the activity or change how it's displayed at certain times.
+    ///
+    /// Defaults to an empty schedule.
+    pub fn schedule(&mut self, schedule: Schedule) -> &mut Self {
+        self.schedule = schedule;
+        self
+    }
+
+    /// Only display these `MediaType`s, can be combined with the blacklist.
+    ///
+    /// Defaults to `Vec::new()`, which allows every `MediaType`.
+    pub fn whitelist_media_types(&mut self, media_types: Vec<MediaType>) -> &mut Self {
+        self.server.whitelist_media_types = media_types;
+        self
+    }
+
+    /// Only display content from these libraries, can be combined with the blacklist.
+    ///
+    /// Defaults to `Vec::new()`, which allows every library.
+    pub fn whitelist_libraries(&mut self, libraries: Vec<String>) -> &mut Self {
+        self.server.whitelist_libraries = libraries;
+        self
+    }
+
+    /// Watch another jellyfin server as well as the one set on this builder,
+    /// the options set on this builder only apply to its own server.
+    ///
+    /// When more than one server is playing something, see `ServerBuilder::priority()`
+    /// for which one is displayed. This builder's own server can be given a priority
+    /// with `ClientBuilder::priority()`.
+    ///
+    /// Defaults to no other servers.
+    pub fn server(&mut self, server: ServerBuilder) -> &mut Self {
+        self.servers.push(server);
+        self
+    }
+
+    /// Show what another player is playing, see [SessionSource].
+    ///
+    /// Sources come after the jellyfin servers when priorities are tied.
+    /// If sources are added the url, api key and usernames can be left out,
+    /// in which case no jellyfin server is watched by this builder.
+    ///
+    /// Defaults to no other sources.
+    pub fn source<T: SessionSource + 'static>(&mut self, source: T) -> &mut Self {
+        self.sources.push(Box::new(source));
+        self
+    }
+
+    /// Show the activity on discord.
+    ///
+    /// Defaults to `true`.
+    pub fn discord(&mut self, discord: bool) -> &mut Self {
+        self.discord = discord;
+        self
+    }
+
+    /// Show the activity somewhere else as well, see [PresenceSink].
+    ///
+    /// Defaults to no other sinks.
+    pub fn sink<T: PresenceSink + 'static>(&mut self, sink: T) -> &mut Self {
+        self.sinks.push(Box::new(sink));
+        self
+    }
+
+    /// Which media server software `ClientBuilder::url()` points to, see [ServerKind].
+    ///
+    /// Defaults to `ServerKind::Jellyfin`.
+    pub fn server_kind(&mut self, kind: ServerKind) -> &mut Self {
+        self.server.kind = kind;
+        self
+    }
+
+    /// Priority of the server set on this builder, see `ServerBuilder::priority()`.
+    ///
+    /// Defaults to `0`.
+    pub fn priority(&mut self, priority: i32) -> &mut Self {
+        self.server.priority = priority;
         self
     }
 
@@ -1136,6 +1867,18 @@ impl ClientBuilder {
         self
     }
 
+    /// Crop, pad or decorate images before they're uploaded to an image host.
+    ///
+    /// Only used when an image host such as imgur is enabled,
+    /// since Discord can't display images that aren't hosted somewhere.
+    ///
+    /// Defaults to `None`.
+    #[cfg(feature = "transform")]
+    pub fn image_transform(&mut self, transform: ImageTransform) -> &mut Self {
+        self.image_transform = Some(transform);
+        self
+    }
+
     /// Text to be displayed when hovering the large activity image in Discord
     ///
     /// Empty by default
@@ -1158,45 +1901,67 @@ impl ClientBuilder {
     /// let mut client = builder.build().unwrap();
     /// ```
     pub fn build(self) -> JfResult<Client> {
-        if self.url.is_empty() || self.usernames.is_empty() || self.api_key.is_empty() {
-            return Err(Box::new(JfError::MissingRequiredValues));
+        #[cfg(feature = "transform")]
+        if self.image_transform.is_some() && !self.use_imgur {
+            warn!(
+                "Image transformations require an image host like imgur, images will be used as-is"
+            );
         }
 
-        let mut headers = HeaderMap::new();
+        // Only other sources are used when no jellyfin server was set on the builder
+        let sources_only = !self.sources.is_empty()
+            && self.server.url.is_empty()
+            && self.server.api_key.is_empty()
+            && self.server.usernames.is_empty();
 
-        headers.insert(
-            AUTHORIZATION,
-            format!("MediaBrowser Token=\"{}\"", self.api_key).parse()?,
-        );
-        headers.insert("X-Emby-Token", self.api_key.parse()?);
+        let mut sources = (!sources_only)
+            .then_some(self.server)
+            .into_iter()
+            .chain(self.servers)
+            .map(|server| Ok(Box::new(server.build()?) as Box<dyn SessionSource>))
+            .collect::<JfResult<Vec<_>>>()?;
+        sources.extend(self.sources);
 
         Ok(Client {
-            discord_ipc_client: DiscordIpcClient::new(&self.client_id)?,
-            url: self.url.parse()?,
-            reqwest: reqwest::blocking::Client::builder()
-                .default_headers(headers)
-                .danger_accept_invalid_certs(self.self_signed)
-                .build()?,
-            usernames: self.usernames,
+            discord: match self.discord {
+                true => Some(DiscordSink {
+                    client: DiscordIpcClient::new(&self.client_id)?,
+                }),
+                false => None,
+            },
+            sinks: self.sinks,
+            sources,
+            source: 0,
             buttons: self.buttons,
+            dynamic_buttons: DynamicButtons {
+                providers: self.dynamic_button_providers,
+                // Sorted first so it's always the same label that wins
+                // when two providers only differ in case
+                labels: self
+                    .dynamic_button_labels
+                    .into_iter()
+                    .collect::<BTreeMap<_, _>>()
+                    .into_iter()
+                    .map(|(provider, label)| (provider.to_lowercase(), label))
+                    .collect(),
+            },
             session: None,
             music_display_options: DisplayOptions {
                 separator: self.music_separator,
                 display: self.music_display,
+                buttons: self.music_buttons,
             },
             movies_display_options: DisplayOptions {
                 separator: self.movies_separator,
                 display: self.movies_display,
+                buttons: self.movies_buttons,
             },
             episodes_display_options: DisplayOptions {
                 separator: self.episodes_separator,
                 display: self.episodes_display,
+                buttons: self.episodes_buttons,
             },
-            blacklist: Blacklist {
-                media_types: self.blacklist_media_types,
-                libraries_names: self.blacklist_libraries,
-                libraries: BlacklistedLibraries::Uninitialized,
-            },
+            schedule: CompiledSchedule::compile(self.schedule)?,
             show_paused: self.show_paused,
             show_images: self.show_images,
             imgur_options: ImgurOptions {
@@ -1204,6 +1969,8 @@ impl ClientBuilder {
                 client_id: self.imgur_client_id,
                 urls_location: self.imgur_urls_file_location,
             },
+            #[cfg(feature = "transform")]
+            image_transform: self.image_transform,
             large_image_text: self.large_image_text,
         })
     }
@@ -0,0 +1,280 @@
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+use crate::jellyfin::NowPlayingItem;
+use crate::MediaType;
+
+/// Rule used to hide content based on its metadata.
+///
+/// Every condition that is set has to match for the rule to match,
+/// use `any` and `all` to combine several rules.
+///
+/// # Example
+/// ```
+/// use jellyfin_rpc::ContentRule;
+///
+/// // Hide anything rated R or higher that's tagged "Horror"
+/// let rule = ContentRule {
+///     min_rating: Some("R".to_string()),
+///     tags: Some(vec!["Horror".to_string()]),
+///     ..Default::default()
+/// };
+/// ```
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Default)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct ContentRule {
+    /// Matches if the content is one of these `MediaType`s.
+    pub media_types: Option<Vec<MediaType>>,
+    /// Matches if the content has any of these genres.
+    pub genres: Option<Vec<String>>,
+    /// Matches if the content, or its season, series or album, has any of these jellyfin tags.
+    pub tags: Option<Vec<String>>,
+    /// Matches if the content is made by any of these studios.
+    pub studios: Option<Vec<String>>,
+    /// Matches if the parental rating of the content is the same as or higher than this one.
+    ///
+    /// # Example
+    /// `"PG-13"`, `"TV-MA"` or `"16"`
+    pub min_rating: Option<String>,
+    /// Regular expression matched against the title.
+    pub title: Option<String>,
+    /// Regular expression matched against the series name.
+    pub series: Option<String>,
+    /// Regular expression matched against the album name.
+    pub album: Option<String>,
+    /// Regular expression matched against the path of the file.
+    pub path: Option<String>,
+    /// Matches if at least one of these rules match.
+    pub any: Option<Vec<ContentRule>>,
+    /// Matches if every one of these rules match.
+    pub all: Option<Vec<ContentRule>>,
+    /// What to do with content matching the rule.
+    ///
+    /// Defaults to the action set with `ClientBuilder::blacklist_action()`.
+    pub action: Option<BlacklistAction>,
+}
+
+/// What to do with content that is blacklisted.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Default)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "snake_case")]
+pub enum BlacklistAction {
+    /// Don't display any activity.
+    #[default]
+    Hide,
+    /// Display placeholder text and image instead of the content.
+    Redact {
+        /// Defaults to "Watching something", "Listening to something" or "Reading something".
+        text: Option<String>,
+        /// URL to an image, defaults to the jellyfin logo.
+        image: Option<String>,
+    },
+    /// Only display the kind of content, for example "Watching a movie".
+    MediaType,
+}
+
+impl BlacklistAction {
+    /// Used to pick an action when several apply, higher hides more.
+    pub(crate) fn restrictiveness(&self) -> u8 {
+        match self {
+            BlacklistAction::Hide => 2,
+            BlacklistAction::Redact { .. } => 1,
+            BlacklistAction::MediaType => 0,
+        }
+    }
+
+    /// Text that replaces the first line of the activity.
+    pub(crate) fn details(&self, media_type: MediaType) -> String {
+        match self {
+            BlacklistAction::Redact {
+                text: Some(text), ..
+            } => text.clone(),
+            BlacklistAction::MediaType => match media_type {
+                MediaType::Movie => "Watching a movie",
+                MediaType::Episode => "Watching a show",
+                MediaType::LiveTv => "Watching live TV",
+                MediaType::Music => "Listening to music",
+                MediaType::Book => "Reading a book",
+                MediaType::AudioBook => "Listening to an audiobook",
+                MediaType::None => "Watching something",
+            }
+            .to_string(),
+            _ => match media_type {
+                MediaType::Music | MediaType::AudioBook => "Listening to something",
+                MediaType::Book => "Reading something",
+                _ => "Watching something",
+            }
+            .to_string(),
+        }
+    }
+
+    /// Image that replaces the image of the content.
+    pub(crate) fn image(&self) -> Option<&str> {
+        match self {
+            BlacklistAction::Redact { image, .. } => image.as_deref(),
+            _ => None,
+        }
+    }
+}
+
+/// [ContentRule] with its regular expressions and rating compiled.
+pub(crate) struct Rule {
+    media_types: Option<Vec<MediaType>>,
+    genres: Option<Vec<String>>,
+    tags: Option<Vec<String>>,
+    studios: Option<Vec<String>>,
+    min_rating: Option<u32>,
+    title: Option<Regex>,
+    series: Option<Regex>,
+    album: Option<Regex>,
+    path: Option<Regex>,
+    any: Option<Vec<Rule>>,
+    all: Option<Vec<Rule>>,
+    pub(crate) action: Option<BlacklistAction>,
+}
+
+impl Rule {
+    pub(crate) fn compile(rule: &ContentRule) -> Result<Self, Box<dyn std::error::Error>> {
+        let regex = |pattern: &Option<String>| pattern.as_deref().map(Regex::new).transpose();
+        let rules = |rules: &Option<Vec<ContentRule>>| {
+            rules
+                .as_ref()
+                .map(|rules| rules.iter().map(Rule::compile).collect())
+                .transpose()
+        };
+
+        let min_rating = match &rule.min_rating {
+            Some(rating) => Some(
+                rating_level(rating)
+                    .ok_or_else(|| format!("unknown parental rating: {}", rating))?,
+            ),
+            None => None,
+        };
+
+        Ok(Self {
+            media_types: rule.media_types.clone(),
+            genres: rule.genres.clone(),
+            tags: rule.tags.clone(),
+            studios: rule.studios.clone(),
+            min_rating,
+            title: regex(&rule.title)?,
+            series: regex(&rule.series)?,
+            album: regex(&rule.album)?,
+            path: regex(&rule.path)?,
+            any: rules(&rule.any)?,
+            all: rules(&rule.all)?,
+            action: rule.action.clone(),
+        })
+    }
+
+    /// Check whether every condition in the rule matches the item,
+    /// a rule without any conditions never matches.
+    ///
+    /// `tags` are the tags of the item and its parents,
+    /// jellyfin doesn't include them in the session so they have to be fetched separately.
+    pub(crate) fn matches(&self, item: &NowPlayingItem, tags: &[String]) -> bool {
+        let mut conditions: Vec<bool> = Vec::new();
+
+        if let Some(media_types) = &self.media_types {
+            conditions.push(media_types.contains(&item.media_type));
+        }
+
+        if let Some(genres) = &self.genres {
+            conditions.push(contains_any(item.genres.as_deref(), genres));
+        }
+
+        if let Some(wanted_tags) = &self.tags {
+            conditions.push(contains_any(Some(tags), wanted_tags));
+        }
+
+        if let Some(studios) = &self.studios {
+            let item_studios: Vec<String> = item
+                .studios
+                .iter()
+                .flatten()
+                .map(|studio| studio.name.clone())
+                .chain(item.series_studio.clone())
+                .collect();
+
+            conditions.push(contains_any(Some(&item_studios), studios));
+        }
+
+        if let Some(min_rating) = self.min_rating {
+            conditions.push(
+                item.official_rating
+                    .as_deref()
+                    .and_then(rating_level)
+                    .is_some_and(|level| level >= min_rating),
+            );
+        }
+
+        for (regex, value) in [
+            (&self.title, Some(&item.name)),
+            (&self.series, item.series_name.as_ref()),
+            (&self.album, item.album.as_ref()),
+            (&self.path, item.path.as_ref()),
+        ] {
+            if let Some(regex) = regex {
+                conditions.push(value.is_some_and(|value| regex.is_match(value)));
+            }
+        }
+
+        if let Some(rules) = &self.any {
+            conditions.push(rules.iter().any(|rule| rule.matches(item, tags)));
+        }
+
+        if let Some(rules) = &self.all {
+            conditions.push(rules.iter().all(|rule| rule.matches(item, tags)));
+        }
+
+        !conditions.is_empty() && conditions.into_iter().all(|c| c)
+    }
+
+    /// Whether the rule or one of its nested rules has a `tags` condition.
+    pub(crate) fn uses_tags(&self) -> bool {
+        self.tags.is_some()
+            || [&self.any, &self.all]
+                .into_iter()
+                .flatten()
+                .flatten()
+                .any(Rule::uses_tags)
+    }
+}
+
+fn contains_any(values: Option<&[String]>, wanted: &[String]) -> bool {
+    values.unwrap_or_default().iter().any(|value| {
+        wanted
+            .iter()
+            .any(|wanted| wanted.eq_ignore_ascii_case(value))
+    })
+}
+
+/// Converts a parental rating to the minimum age it's meant for.
+///
+/// Knows the US movie and TV ratings by name,
+/// everything else (`"FSK-16"`, `"DE-12"`, `"15"`) uses the age in the rating.
+pub(crate) fn rating_level(rating: &str) -> Option<u32> {
+    let rating = rating.trim().to_uppercase();
+    let rating = rating.strip_prefix("US-").unwrap_or(&rating);
+
+    let level = match rating {
+        "G" | "E" | "U" | "TV-Y" | "TV-G" | "APPROVED" => 0,
+        "TV-Y7" | "TV-Y7-FV" => 7,
+        "PG" | "TV-PG" => 10,
+        "PG-13" => 13,
+        "TV-14" => 14,
+        "R" | "TV-MA" => 17,
+        "NC-17" | "X" | "XXX" | "AO" => 18,
+        _ => {
+            let age: String = rating
+                .chars()
+                .skip_while(|c| !c.is_ascii_digit())
+                .take_while(|c| c.is_ascii_digit())
+                .collect();
+
+            return age.parse().ok();
+        }
+    };
+
+    Some(level)
+}
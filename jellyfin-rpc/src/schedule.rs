@@ -0,0 +1,211 @@
+use chrono::{Datelike, Local, NaiveTime, TimeZone, Utc, Weekday};
+use chrono_tz::Tz;
+use serde::{Deserialize, Serialize};
+
+use crate::{DisplayFormat, JfResult, MediaType};
+
+/// Times where presence is hidden or displayed differently.
+///
+/// # Example
+/// ```
+/// use jellyfin_rpc::{Schedule, TimeWindow};
+///
+/// // Don't show anything during work hours on mondays and fridays
+/// let schedule = Schedule {
+///     timezone: Some("Europe/Oslo".to_string()),
+///     quiet_hours: vec![TimeWindow {
+///         days: Some(vec!["mon".parse().unwrap(), "fri".parse().unwrap()]),
+///         start: "08:00".to_string(),
+///         end: "16:00".to_string(),
+///     }],
+///     ..Default::default()
+/// };
+/// ```
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Default)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(default)]
+pub struct Schedule {
+    /// IANA timezone the windows are in.
+    ///
+    /// # Example
+    /// `"Europe/Oslo"` or `"America/New_York"`
+    ///
+    /// Defaults to the timezone of the system.
+    pub timezone: Option<String>,
+    /// No activity is displayed during these windows.
+    pub quiet_hours: Vec<TimeWindow>,
+    /// Display formats that replace the usual ones during a window,
+    /// the first window that is open is used.
+    pub displays: Vec<ScheduledDisplay>,
+}
+
+/// A time of day, optionally limited to some days of the week.
+///
+/// Windows where `end` is before `start` continue past midnight into the next day.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct TimeWindow {
+    /// Days the window opens on.
+    ///
+    /// # Example
+    /// `["mon", "tue", "wed"]`
+    ///
+    /// Defaults to every day.
+    #[cfg_attr(feature = "schema", schemars(schema_with = "weekdays_schema"))]
+    pub days: Option<Vec<Weekday>>,
+    /// When the window opens, in 24 hour `HH:MM` format.
+    pub start: String,
+    /// When the window closes, in 24 hour `HH:MM` format.
+    pub end: String,
+}
+
+/// Display formats used while `window` is open.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct ScheduledDisplay {
+    #[serde(flatten)]
+    pub window: TimeWindow,
+    /// Replaces the format set with `ClientBuilder::music_display()`.
+    pub music: Option<DisplayFormat>,
+    /// Replaces the format set with `ClientBuilder::movies_display()`.
+    pub movies: Option<DisplayFormat>,
+    /// Replaces the format set with `ClientBuilder::episodes_display()`.
+    pub episodes: Option<DisplayFormat>,
+}
+
+/// Days are read by chrono as short or full english names in any case,
+/// the schema accepts them in lowercase and capitalized.
+#[cfg(feature = "schema")]
+fn weekdays_schema(_: &mut schemars::SchemaGenerator) -> schemars::Schema {
+    let days: Vec<String> = [
+        "monday",
+        "tuesday",
+        "wednesday",
+        "thursday",
+        "friday",
+        "saturday",
+        "sunday",
+    ]
+    .iter()
+    .flat_map(|day| [&day[..3], day])
+    .flat_map(|day| [day.to_string(), day[..1].to_uppercase() + &day[1..]])
+    .collect();
+
+    schemars::json_schema!({
+        "type": ["array", "null"],
+        "items": {
+            "type": "string",
+            "enum": days
+        }
+    })
+}
+
+/// [Schedule] with its timezone and times parsed.
+pub(crate) struct CompiledSchedule {
+    timezone: Option<Tz>,
+    quiet_hours: Vec<Window>,
+    displays: Vec<(Window, ScheduledDisplay)>,
+}
+
+impl CompiledSchedule {
+    pub(crate) fn compile(schedule: Schedule) -> JfResult<Self> {
+        let timezone = match &schedule.timezone {
+            Some(timezone) => Some(
+                timezone
+                    .parse::<Tz>()
+                    .map_err(|_| format!("unknown timezone: {}", timezone))?,
+            ),
+            None => None,
+        };
+
+        Ok(Self {
+            timezone,
+            quiet_hours: schedule
+                .quiet_hours
+                .iter()
+                .map(Window::compile)
+                .collect::<JfResult<_>>()?,
+            displays: schedule
+                .displays
+                .into_iter()
+                .map(|display| Ok((Window::compile(&display.window)?, display)))
+                .collect::<JfResult<_>>()?,
+        })
+    }
+
+    /// Day of the week and time of day in the configured timezone.
+    fn now(&self) -> (Weekday, NaiveTime) {
+        let now = match self.timezone {
+            Some(timezone) => timezone
+                .from_utc_datetime(&Utc::now().naive_utc())
+                .naive_local(),
+            None => Local::now().naive_local(),
+        };
+
+        (now.weekday(), now.time())
+    }
+
+    pub(crate) fn is_quiet(&self) -> bool {
+        if self.quiet_hours.is_empty() {
+            return false;
+        }
+
+        let (day, time) = self.now();
+        self.quiet_hours.iter().any(|w| w.contains(day, time))
+    }
+
+    /// Display format that replaces the usual one for `media_type` right now, if any.
+    pub(crate) fn display(&self, media_type: MediaType) -> Option<&DisplayFormat> {
+        if self.displays.is_empty() {
+            return None;
+        }
+
+        let (day, time) = self.now();
+        self.displays
+            .iter()
+            .filter(|(window, _)| window.contains(day, time))
+            .find_map(|(_, display)| match media_type {
+                MediaType::Music => display.music.as_ref(),
+                MediaType::Movie => display.movies.as_ref(),
+                MediaType::Episode => display.episodes.as_ref(),
+                _ => None,
+            })
+    }
+}
+
+/// [TimeWindow] with its times parsed.
+pub(crate) struct Window {
+    days: Option<Vec<Weekday>>,
+    start: NaiveTime,
+    end: NaiveTime,
+}
+
+impl Window {
+    pub(crate) fn compile(window: &TimeWindow) -> JfResult<Self> {
+        let time = |time: &str| {
+            NaiveTime::parse_from_str(time.trim(), "%H:%M")
+                .map_err(|_| format!("invalid time, expected HH:MM: {}", time))
+        };
+
+        Ok(Self {
+            days: window.days.clone(),
+            start: time(&window.start)?,
+            end: time(&window.end)?,
+        })
+    }
+
+    fn opens_on(&self, day: Weekday) -> bool {
+        self.days.as_ref().is_none_or(|days| days.contains(&day))
+    }
+
+    /// Whether the window is open on `day` at `time`,
+    /// a window with the same start and end is open for 24 hours.
+    pub(crate) fn contains(&self, day: Weekday, time: NaiveTime) -> bool {
+        if self.start < self.end {
+            self.opens_on(day) && self.start <= time && time < self.end
+        } else {
+            (self.opens_on(day) && time >= self.start)
+                || (self.opens_on(day.pred()) && time < self.end)
+        }
+    }
+}
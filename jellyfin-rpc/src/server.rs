@@ -0,0 +1,776 @@
+use log::{debug, warn};
+use reqwest::header::{HeaderMap, AUTHORIZATION};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::time::{Duration, SystemTime};
+use url::Url;
+
+use crate::jellyfin::{
+    ItemId, Items, NowPlayingItem, PublicSystemInfo, RawSession, Session, User, VirtualFolder,
+};
+use crate::rules::Rule;
+use crate::{
+    Blacklist, BlacklistAction, BlacklistedLibraries, ContentRule, JellyfinButton, JfError,
+    JfResult, MediaType, SessionSource,
+};
+
+/// Which media server software a server runs.
+///
+/// Emby's API is close enough to jellyfin's that the same client works for both,
+/// this only changes the few places where they differ.
+#[derive(Default, Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "lowercase")]
+pub enum ServerKind {
+    /// A jellyfin server.
+    #[default]
+    Jellyfin,
+    /// An Emby server, its API is served under `/emby/`.
+    Emby,
+}
+
+/// Emby item types that have a different name on jellyfin.
+const EMBY_ITEM_TYPES: &[(&str, &str)] = &[("Program", "TvChannel")];
+
+impl ServerKind {
+    /// Url of an API endpoint on the server at `url`.
+    pub(crate) fn endpoint(self, url: &Url, path: &str) -> Result<Url, url::ParseError> {
+        match self {
+            ServerKind::Jellyfin => url.join(path),
+            ServerKind::Emby => url.join(&format!("emby/{}", path)),
+        }
+    }
+
+    /// Headers authenticating every request with the api key.
+    pub(crate) fn headers(self, api_key: &str) -> JfResult<HeaderMap> {
+        let mut headers = HeaderMap::new();
+        let authorization = format!("MediaBrowser Token=\"{}\"", api_key).parse()?;
+
+        match self {
+            ServerKind::Jellyfin => {
+                headers.insert(AUTHORIZATION, authorization);
+                headers.insert("X-Emby-Token", api_key.parse()?);
+            }
+            ServerKind::Emby => {
+                headers.insert("X-Emby-Authorization", authorization);
+                headers.insert("X-Emby-Token", api_key.parse()?);
+            }
+        }
+
+        Ok(headers)
+    }
+
+    /// Link to an item on the web interface, `{public-url}`, `{item-id}` and `{server-id}`
+    /// are filled in by the caller.
+    pub(crate) fn item_link(self) -> &'static str {
+        match self {
+            ServerKind::Jellyfin => "{public-url}/web/#/details?id={item-id}&serverId={server-id}",
+            ServerKind::Emby => {
+                "{public-url}/web/index.html#!/item?id={item-id}&serverId={server-id}"
+            }
+        }
+    }
+
+    /// Parses the response of the `Sessions` endpoint.
+    pub(crate) fn parse_sessions(self, body: &str) -> JfResult<Vec<RawSession>> {
+        if self == ServerKind::Jellyfin {
+            return Ok(serde_json::from_str(body)?);
+        }
+
+        let mut sessions: Vec<Value> = serde_json::from_str(body)?;
+
+        for item in sessions
+            .iter_mut()
+            .filter_map(|session| session.get_mut("NowPlayingItem"))
+        {
+            let Some(Value::String(item_type)) = item.get_mut("Type") else {
+                continue;
+            };
+
+            if let Some((_, name)) = EMBY_ITEM_TYPES.iter().find(|(emby, _)| emby == item_type) {
+                *item_type = name.to_string();
+            }
+        }
+
+        Ok(sessions
+            .into_iter()
+            .map(serde_json::from_value)
+            .collect::<Result<_, _>>()?)
+    }
+}
+
+/// A jellyfin server to watch together with the one set on the [ClientBuilder](crate::ClientBuilder),
+/// see `ClientBuilder::server()`.
+///
+/// Each server has its own credentials, users and blacklist.
+///
+/// # Example
+/// ```
+/// use jellyfin_rpc::{ClientBuilder, ServerBuilder};
+///
+/// let mut friend = ServerBuilder::new();
+/// friend.name("friend")
+///     .url("https://jellyfin.friend.example.com")
+///     .api_key("efgh5678")
+///     .username("me");
+///
+/// let mut builder = ClientBuilder::new();
+/// builder.api_key("abcd1234")
+///     .url("https://jellyfin.example.com")
+///     .username("user")
+///     .server(friend);
+///
+/// let client = builder.build().unwrap();
+/// ```
+#[derive(Clone)]
+pub struct ServerBuilder {
+    pub(crate) name: Option<String>,
+    pub(crate) kind: ServerKind,
+    pub(crate) url: String,
+    pub(crate) public_url: Option<String>,
+    pub(crate) api_key: String,
+    pub(crate) self_signed: bool,
+    pub(crate) usernames: Vec<String>,
+    pub(crate) priority: i32,
+    pub(crate) jellyfin_button_user: Option<String>,
+    pub(crate) blacklist_media_types: Vec<MediaType>,
+    pub(crate) blacklist_libraries: Vec<String>,
+    pub(crate) whitelist_media_types: Vec<MediaType>,
+    pub(crate) whitelist_libraries: Vec<String>,
+    pub(crate) blacklist_rules: Vec<ContentRule>,
+    pub(crate) private_tags: Vec<String>,
+    pub(crate) blacklist_action: BlacklistAction,
+    pub(crate) blacklist_cache_ttl: u64,
+    pub(crate) blacklist_fail_closed: bool,
+}
+
+impl Default for ServerBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ServerBuilder {
+    /// Returns a ServerBuilder with some default options set
+    pub fn new() -> Self {
+        Self {
+            name: None,
+            kind: ServerKind::default(),
+            url: String::new(),
+            public_url: None,
+            api_key: String::new(),
+            self_signed: false,
+            usernames: Vec::new(),
+            priority: 0,
+            jellyfin_button_user: None,
+            blacklist_media_types: Vec::new(),
+            blacklist_libraries: Vec::new(),
+            whitelist_media_types: Vec::new(),
+            whitelist_libraries: Vec::new(),
+            blacklist_rules: Vec::new(),
+            private_tags: Vec::new(),
+            blacklist_action: BlacklistAction::default(),
+            blacklist_cache_ttl: 3600,
+            blacklist_fail_closed: false,
+        }
+    }
+
+    /// Name of the server, only used in logs.
+    ///
+    /// Defaults to the host of `ServerBuilder::url()`.
+    pub fn name<T: Into<String>>(&mut self, name: T) -> &mut Self {
+        self.name = Some(name.into());
+        self
+    }
+
+    /// Which media server software the server runs.
+    ///
+    /// Defaults to `ServerKind::Jellyfin`.
+    pub fn kind(&mut self, kind: ServerKind) -> &mut Self {
+        self.kind = kind;
+        self
+    }
+
+    /// Jellyfin URL of the server.
+    ///
+    /// Has no default.
+    pub fn url<T: Into<String>>(&mut self, url: T) -> &mut Self {
+        self.url = url.into();
+        self
+    }
+
+    /// Jellyfin URL that is reachable by the people viewing your activity,
+    /// used for the `{public-url}` placeholder in buttons.
+    ///
+    /// Defaults to the value set in `ServerBuilder::url()`.
+    pub fn public_url<T: Into<String>>(&mut self, url: T) -> &mut Self {
+        self.public_url = Some(url.into());
+        self
+    }
+
+    /// Jellyfin API Key for the server.
+    ///
+    /// Has no default.
+    pub fn api_key<T: Into<String>>(&mut self, api_key: T) -> &mut Self {
+        self.api_key = api_key.into();
+        self
+    }
+
+    /// Controls the use of certificate validation in reqwest.
+    ///
+    /// Defaults to `false`.
+    pub fn self_signed(&mut self, self_signed: bool) -> &mut Self {
+        self.self_signed = self_signed;
+        self
+    }
+
+    /// Usernames that should be matched when checking the sessions of the server.
+    ///
+    /// Has no default.
+    pub fn usernames(&mut self, usernames: Vec<String>) -> &mut Self {
+        self.usernames = usernames;
+        self
+    }
+
+    /// Same as `ServerBuilder::usernames()` but will only accept a single username.
+    ///
+    /// Has no default.
+    pub fn username<T: Into<String>>(&mut self, username: T) -> &mut Self {
+        self.usernames = vec![username.into()];
+        self
+    }
+
+    /// Which server is displayed when several are playing something,
+    /// the highest priority wins and servers added first win ties.
+    /// Something that is playing always wins over something that is paused.
+    ///
+    /// Defaults to `0`.
+    pub fn priority(&mut self, priority: i32) -> &mut Self {
+        self.priority = priority;
+        self
+    }
+
+    /// Only show buttons linking to the item on jellyfin if this user of the server can access it.
+    ///
+    /// Defaults to `None`, which always shows the button.
+    pub fn jellyfin_button_user<T: Into<String>>(&mut self, username: T) -> &mut Self {
+        self.jellyfin_button_user = Some(username.into());
+        self
+    }
+
+    /// Blacklist certain `MediaType`s so they don't display.
+    ///
+    /// Defaults to `Vec::new()`.
+    pub fn blacklist_media_types(&mut self, media_types: Vec<MediaType>) -> &mut Self {
+        self.blacklist_media_types = media_types;
+        self
+    }
+
+    /// Blacklist certain libraries of the server so they don't display.
+    ///
+    /// Defaults to `Vec::new()`.
+    pub fn blacklist_libraries(&mut self, libraries: Vec<String>) -> &mut Self {
+        self.blacklist_libraries = libraries;
+        self
+    }
+
+    /// Only display these `MediaType`s, can be combined with the blacklist.
+    ///
+    /// Defaults to `Vec::new()`, which allows every `MediaType`.
+    pub fn whitelist_media_types(&mut self, media_types: Vec<MediaType>) -> &mut Self {
+        self.whitelist_media_types = media_types;
+        self
+    }
+
+    /// Only display content from these libraries of the server, can be combined with the blacklist.
+    ///
+    /// Defaults to `Vec::new()`, which allows every library.
+    pub fn whitelist_libraries(&mut self, libraries: Vec<String>) -> &mut Self {
+        self.whitelist_libraries = libraries;
+        self
+    }
+
+    /// Hide content matching any of these rules,
+    /// see [ContentRule] for what can be matched against.
+    ///
+    /// Defaults to `Vec::new()`.
+    pub fn blacklist_rules(&mut self, rules: Vec<ContentRule>) -> &mut Self {
+        self.blacklist_rules = rules;
+        self
+    }
+
+    /// Hide content if it, or its season, series or album has one of these jellyfin tags.
+    ///
+    /// Defaults to `Vec::new()`.
+    pub fn private_tags(&mut self, tags: Vec<String>) -> &mut Self {
+        self.private_tags = tags;
+        self
+    }
+
+    /// What to do with blacklisted content, a [ContentRule] can override this with its own action.
+    ///
+    /// Defaults to `BlacklistAction::Hide`.
+    pub fn blacklist_action(&mut self, action: BlacklistAction) -> &mut Self {
+        self.blacklist_action = action;
+        self
+    }
+
+    /// How many seconds the library list is kept before it's loaded from the server again.
+    ///
+    /// Defaults to `3600`.
+    pub fn blacklist_cache_ttl(&mut self, seconds: u64) -> &mut Self {
+        self.blacklist_cache_ttl = seconds;
+        self
+    }
+
+    /// Hide everything from the server while its library list can't be loaded,
+    /// and items whose tags can't be loaded when there are private tags or tag rules.
+    ///
+    /// Defaults to `false`.
+    pub fn blacklist_fail_closed(&mut self, val: bool) -> &mut Self {
+        self.blacklist_fail_closed = val;
+        self
+    }
+
+    pub(crate) fn build(self) -> JfResult<Server> {
+        if self.url.is_empty() || self.usernames.is_empty() || self.api_key.is_empty() {
+            return Err(Box::new(JfError::MissingRequiredValues));
+        }
+
+        let headers = self.kind.headers(&self.api_key)?;
+
+        let url: Url = self.url.parse()?;
+        let public_url = match &self.public_url {
+            Some(public_url) => public_url.parse()?,
+            None => url.clone(),
+        };
+
+        Ok(Server {
+            name: self
+                .name
+                .unwrap_or_else(|| url.host_str().unwrap_or_default().to_string()),
+            reqwest: reqwest::blocking::Client::builder()
+                .default_headers(headers)
+                .danger_accept_invalid_certs(self.self_signed)
+                .build()?,
+            kind: self.kind,
+            url,
+            public_url,
+            usernames: self.usernames,
+            priority: self.priority,
+            server_id: None,
+            server_id_retry: None,
+            jellyfin_button: JellyfinButton {
+                access_user: self.jellyfin_button_user,
+                ..Default::default()
+            },
+            blacklist: Blacklist {
+                media_types: self.blacklist_media_types,
+                libraries_names: self.blacklist_libraries,
+                whitelist_media_types: self.whitelist_media_types,
+                whitelist_libraries_names: self.whitelist_libraries,
+                rules: self
+                    .blacklist_rules
+                    .iter()
+                    .map(Rule::compile)
+                    .collect::<JfResult<_>>()?,
+                action: self.blacklist_action,
+                private_tags: self.private_tags,
+                item_tags: HashMap::new(),
+                item_ancestors: HashMap::new(),
+                libraries: BlacklistedLibraries::Uninitialized,
+                cache_ttl: Duration::from_secs(self.blacklist_cache_ttl),
+                fail_closed: self.blacklist_fail_closed,
+            },
+        })
+    }
+}
+
+/// How long to wait before asking for the server id again after it failed the first time,
+/// every failure after that doubles it up to [`MAX_SERVER_ID_RETRY`].
+const SERVER_ID_RETRY: Duration = Duration::from_secs(30);
+const MAX_SERVER_ID_RETRY: Duration = Duration::from_secs(3600);
+
+/// A jellyfin server and everything that is cached about it.
+pub(crate) struct Server {
+    pub(crate) name: String,
+    pub(crate) kind: ServerKind,
+    pub(crate) url: Url,
+    pub(crate) public_url: Url,
+    usernames: Vec<String>,
+    pub(crate) priority: i32,
+    pub(crate) reqwest: reqwest::blocking::Client,
+    pub(crate) server_id: Option<String>,
+    /// When to ask for the server id again and how long the last wait was
+    pub(crate) server_id_retry: Option<(SystemTime, Duration)>,
+    pub(crate) jellyfin_button: JellyfinButton,
+    pub(crate) blacklist: Blacklist,
+}
+
+impl Server {
+    /// Url of an API endpoint on the server.
+    pub(crate) fn endpoint(&self, path: &str) -> Result<Url, url::ParseError> {
+        self.kind.endpoint(&self.url, path)
+    }
+
+    /// Url of an item as seen by a user, it fails if the user can't access it.
+    pub(crate) fn user_item_endpoint(
+        &self,
+        user_id: &str,
+        item_id: &str,
+    ) -> Result<Url, url::ParseError> {
+        self.endpoint(&format!("Users/{}/Items/{}", user_id, item_id))
+    }
+
+    /// Picks the first session of one of the usernames that is playing something.
+    pub(crate) fn find_session(&self, sessions: Vec<RawSession>) -> Option<Session> {
+        for session in sessions {
+            debug!("Session username is {:?}", session.user_name);
+            if let Some(username) = session.user_name.as_ref() {
+                if self
+                    .usernames
+                    .iter()
+                    .all(|u| username.to_lowercase() != u.to_lowercase())
+                {
+                    continue;
+                }
+
+                if session.now_playing_item.is_none() {
+                    continue;
+                }
+                debug!("NowPlayingItem exists");
+
+                if session.play_state.is_none() {
+                    continue;
+                }
+                debug!("PlayState exists");
+
+                let session = session.build();
+
+                if session
+                    .now_playing_item
+                    .extra_type
+                    .as_ref()
+                    .is_some_and(|et| et == "ThemeSong")
+                {
+                    debug!("Session is playing a theme song, continuing loop");
+                    continue;
+                }
+
+                return Some(session);
+            }
+        }
+
+        None
+    }
+
+    fn fetch_server_id(&self) -> JfResult<String> {
+        let info: PublicSystemInfo = self
+            .reqwest
+            .get(self.endpoint("System/Info/Public")?)
+            .send()?
+            .json()?;
+
+        debug!("Server id is {}", info.id);
+
+        Ok(info.id)
+    }
+
+    /// Gets the server id unless it failed recently, waiting longer after every failure.
+    fn prepare_server_id(&mut self) {
+        let wait = match self.server_id_retry {
+            Some((retry_at, _)) if SystemTime::now() < retry_at => return,
+            Some((_, wait)) => (wait * 2).min(MAX_SERVER_ID_RETRY),
+            None => SERVER_ID_RETRY,
+        };
+
+        match self.fetch_server_id() {
+            Ok(server_id) => {
+                self.server_id = Some(server_id);
+                self.server_id_retry = None;
+            }
+            Err(err) => {
+                warn!(
+                    "Failed to get the server id, trying again in {}s: {}",
+                    wait.as_secs(),
+                    err
+                );
+                self.server_id_retry = Some((SystemTime::now() + wait, wait));
+            }
+        }
+    }
+
+    /// Checks whether the jellyfin button user can access the item and caches the result.
+    pub(crate) fn check_item_access(&mut self, item_id: &str) -> JfResult<()> {
+        let ttl = self.blacklist.cache_ttl;
+        self.jellyfin_button
+            .accessible
+            .retain(|_, (_, checked)| checked.elapsed().is_ok_and(|passed| passed <= ttl));
+
+        if self.jellyfin_button.accessible.contains_key(item_id) {
+            return Ok(());
+        }
+
+        let user_id = match &self.jellyfin_button.access_user_id {
+            Some(user_id) => user_id.clone(),
+            None => {
+                let username = self.jellyfin_button.access_user.as_ref().unwrap();
+                let users: Vec<User> = self.reqwest.get(self.endpoint("Users")?).send()?.json()?;
+
+                let user_id = users
+                    .into_iter()
+                    .find(|user| user.name.eq_ignore_ascii_case(username))
+                    .map(|user| user.id)
+                    .ok_or(JfError::UserNotFound)?;
+
+                self.jellyfin_button.access_user_id = Some(user_id.clone());
+                user_id
+            }
+        };
+
+        let accessible = self
+            .reqwest
+            .get(self.user_item_endpoint(&user_id, item_id)?)
+            .send()?
+            .status()
+            .is_success();
+
+        debug!("Item {} accessible: {}", item_id, accessible);
+
+        self.jellyfin_button
+            .accessible
+            .insert(item_id.to_string(), (accessible, SystemTime::now()));
+
+        Ok(())
+    }
+
+    /// Loads the library list if it isn't loaded yet or the cache expired.
+    fn prepare_blacklist(&mut self) {
+        match &self.blacklist.libraries {
+            BlacklistedLibraries::Uninitialized => {
+                self.reload_blacklist();
+            }
+            BlacklistedLibraries::Initialized(_, init_time) => {
+                if SystemTime::now()
+                    .duration_since(*init_time)
+                    .map(|passed| passed > self.blacklist.cache_ttl)
+                    .unwrap_or(false)
+                {
+                    debug!("reloading blacklist after cache expiration");
+                    self.reload_blacklist();
+                }
+            }
+        }
+    }
+
+    pub(crate) fn refresh_blacklist(&mut self) -> JfResult<()> {
+        // Tags, ancestors and access can change as well, so they expire together with the library list
+        self.blacklist.item_tags.clear();
+        self.blacklist.item_ancestors.clear();
+        self.jellyfin_button.accessible.clear();
+        self.blacklist.libraries = BlacklistedLibraries::Uninitialized;
+
+        let libraries = self.fetch_blacklist()?;
+        self.blacklist.libraries = BlacklistedLibraries::Initialized(libraries, SystemTime::now());
+
+        Ok(())
+    }
+
+    /// Fetch the virtual folder list and keep the blacklisted and whitelisted libraries
+    fn fetch_blacklist(&self) -> JfResult<Vec<VirtualFolder>> {
+        let virtual_folders: Vec<VirtualFolder> = self
+            .reqwest
+            .get(self.endpoint("Library/VirtualFolders")?)
+            .send()?
+            .json()?;
+
+        Ok(virtual_folders
+            .into_iter()
+            .filter(|library_folder| {
+                let name = library_folder.name.clone().unwrap_or_default();
+
+                self.blacklist.libraries_names.contains(&name)
+                    || self.blacklist.whitelist_libraries_names.contains(&name)
+            })
+            .collect())
+    }
+
+    /// Fetch the tags of the item and its season, series and album,
+    /// items that are already cached are skipped.
+    fn fetch_item_tags(&mut self, item: &NowPlayingItem) -> JfResult<()> {
+        let ids: Vec<&String> = Blacklist::tagged_ids(item)
+            .into_iter()
+            .filter(|id| !self.blacklist.item_tags.contains_key(*id))
+            .collect();
+
+        if ids.is_empty() {
+            return Ok(());
+        }
+
+        let mut url = self.endpoint("Items")?;
+        url.query_pairs_mut()
+            .append_pair(
+                "Ids",
+                &ids.iter()
+                    .map(|id| id.as_str())
+                    .collect::<Vec<&str>>()
+                    .join(","),
+            )
+            .append_pair("Fields", "Tags");
+
+        let items: Items = self.reqwest.get(url).send()?.json()?;
+
+        let ids: Vec<String> = ids.into_iter().cloned().collect();
+
+        for item in items.items {
+            self.blacklist
+                .item_tags
+                .insert(item.id, item.tags.unwrap_or_default());
+        }
+
+        // Items that weren't returned have no tags we can see, don't ask for them again
+        for id in ids {
+            self.blacklist.item_tags.entry(id).or_default();
+        }
+
+        Ok(())
+    }
+
+    /// Fetch the ids of every folder the item is in, used to find its library.
+    fn fetch_item_ancestors(&mut self, item: &NowPlayingItem) -> JfResult<()> {
+        if self.blacklist.item_ancestors.contains_key(&item.id) {
+            return Ok(());
+        }
+
+        let ancestors: Vec<ItemId> = self
+            .reqwest
+            .get(self.endpoint(&format!("Items/{}/Ancestors", item.id))?)
+            .send()?
+            .error_for_status()?
+            .json()?;
+
+        self.blacklist.item_ancestors.insert(
+            item.id.clone(),
+            ancestors.into_iter().map(|ancestor| ancestor.id).collect(),
+        );
+
+        Ok(())
+    }
+
+    /// Reload the library list from Jellyfin, logging a warning if it fails
+    fn reload_blacklist(&mut self) {
+        if let Err(err) = self.refresh_blacklist() {
+            warn!("Failed to intialize blacklist of {}: {}", self.name, err);
+        }
+    }
+}
+
+impl SessionSource for Server {
+    fn name(&self) -> String {
+        self.name.clone()
+    }
+
+    /// Finds the first session of one of the usernames that is playing something.
+    fn get_session(&mut self) -> JfResult<Option<Session>> {
+        let body = self
+            .reqwest
+            .get(self.endpoint("Sessions")?)
+            .send()?
+            .text()?;
+        let sessions = self.kind.parse_sessions(&body)?;
+
+        debug!("Found {} sessions on {}", sessions.len(), self.name);
+
+        Ok(self.find_session(sessions))
+    }
+
+    fn priority(&self) -> i32 {
+        self.priority
+    }
+
+    fn prepare(&mut self, session: &Session, links: bool) {
+        // Make sure the blacklist cache is loaded/valid
+        self.prepare_blacklist();
+
+        if self.blacklist.needs_tags() {
+            if let Err(err) = self.fetch_item_tags(&session.now_playing_item) {
+                warn!("Failed to get the tags of the item: {}", err);
+            }
+        }
+
+        if self.blacklist.needs_libraries() {
+            if let Err(err) = self.fetch_item_ancestors(&session.now_playing_item) {
+                warn!("Failed to get the ancestors of the item: {}", err);
+            }
+        }
+
+        if !links {
+            return;
+        }
+
+        if self.server_id.is_none() {
+            self.prepare_server_id();
+        }
+
+        if self.jellyfin_button.access_user.is_some() {
+            if let Err(err) = self.check_item_access(&session.now_playing_item.id) {
+                warn!("Failed to check if the item can be linked to: {}", err);
+            }
+        }
+    }
+
+    fn refresh(&mut self) -> JfResult<()> {
+        self.refresh_blacklist()
+    }
+
+    fn check_blacklist(&self, session: &Session) -> Option<BlacklistAction> {
+        self.blacklist.check(&session.now_playing_item)
+    }
+
+    fn image_url(&self, session: &Session) -> JfResult<Url> {
+        // The tag changes whenever the artwork does, which also busts Discord's media proxy cache
+        let image_tag = session.image_tag.as_ref().ok_or(JfError::NoImage)?;
+
+        let path = "Items/".to_string() + &session.item_id + "/Images/Primary";
+
+        let mut image_url = self.endpoint(&path)?;
+        image_url.query_pairs_mut().append_pair("tag", image_tag);
+
+        Ok(image_url)
+    }
+
+    fn image(&self, session: &Session) -> JfResult<Vec<u8>> {
+        Ok(self
+            .reqwest
+            .get(self.image_url(session)?)
+            .send()?
+            .bytes()?
+            .to_vec())
+    }
+
+    fn link(&self, session: &Session) -> Option<String> {
+        if self.jellyfin_button.access_user.is_some()
+            && !self
+                .jellyfin_button
+                .accessible
+                .get(&session.now_playing_item.id)
+                .is_some_and(|(accessible, _)| *accessible)
+        {
+            debug!("Skipping jellyfin button, item isn't accessible");
+            return None;
+        }
+
+        Some(self.kind.item_link().to_string())
+    }
+
+    fn button_placeholders(&self, _session: &Session) -> Vec<(&'static str, String)> {
+        vec![
+            (
+                "{public-url}",
+                self.public_url.as_str().trim_end_matches('/').to_string(),
+            ),
+            ("{server-id}", self.server_id.clone().unwrap_or_default()),
+        ]
+    }
+}
@@ -0,0 +1,169 @@
+use discord_rich_presence::activity::{
+    Activity, ActivityType, Assets, Button as ActButton, Timestamps,
+};
+use discord_rich_presence::{DiscordIpc, DiscordIpcClient};
+use serde::Serialize;
+
+use crate::{Button, JfResult, MediaType};
+
+/// Everything that is shown about the current session, see [PresenceSink].
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Presence {
+    /// First line of the activity, the title by default.
+    pub details: String,
+    /// Second line of the activity.
+    pub state: String,
+    /// Text shown when hovering over the image.
+    pub image_text: String,
+    /// Artwork of the item, or a placeholder image when there is none.
+    pub image_url: String,
+    /// What kind of media is being played.
+    pub media_type: MediaType,
+    /// Whether playback is paused.
+    pub paused: bool,
+    /// Unix timestamp of when playback started, `None` when paused or the media has no length.
+    pub start: Option<i64>,
+    /// Unix timestamp of when playback will end, `None` when paused or the media has no length.
+    pub end: Option<i64>,
+    /// How far into the media playback is, from `0.0` to `1.0`, `None` when redacted.
+    pub progress: Option<f64>,
+    /// At most 2 buttons linking to the item.
+    pub buttons: Vec<Button>,
+}
+
+/// Somewhere the activity is shown, like discord.
+///
+/// Discord is used unless it's turned off with `ClientBuilder::discord()`,
+/// other places can be added with `ClientBuilder::sink()`.
+/// `Client::set_activity()` hands every sink the same [Presence].
+///
+/// # Example
+/// ```
+/// use jellyfin_rpc::{ClientBuilder, Presence, PresenceSink};
+///
+/// struct Log;
+///
+/// impl PresenceSink for Log {
+///     fn name(&self) -> String {
+///         "log".to_string()
+///     }
+///
+///     fn set_presence(&mut self, presence: &Presence) -> Result<(), Box<dyn std::error::Error>> {
+///         println!("{} - {}", presence.details, presence.state);
+///         Ok(())
+///     }
+///
+///     fn clear_presence(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+///         println!("Nothing is playing");
+///         Ok(())
+///     }
+/// }
+///
+/// let mut builder = ClientBuilder::new();
+/// builder.api_key("abcd1234")
+///     .url("https://jellyfin.example.com")
+///     .username("user")
+///     .sink(Log);
+///
+/// let client = builder.build().unwrap();
+/// ```
+pub trait PresenceSink: Send {
+    /// Name of the sink, only used in logs.
+    fn name(&self) -> String;
+
+    /// Called by `Client::connect()` and after the config of the client was updated.
+    fn connect(&mut self) -> JfResult<()> {
+        Ok(())
+    }
+
+    /// Called by `Client::reconnect()`, usually after showing the activity failed.
+    fn reconnect(&mut self) -> JfResult<()> {
+        self.connect()
+    }
+
+    /// Show `presence`, this is called on every update even if nothing changed.
+    fn set_presence(&mut self, presence: &Presence) -> JfResult<()>;
+
+    /// Stop showing anything.
+    fn clear_presence(&mut self) -> JfResult<()>;
+}
+
+/// Shows the activity on discord through its IPC socket.
+pub(crate) struct DiscordSink {
+    pub(crate) client: DiscordIpcClient,
+}
+
+impl PresenceSink for DiscordSink {
+    fn name(&self) -> String {
+        "discord".to_string()
+    }
+
+    fn connect(&mut self) -> JfResult<()> {
+        self.client.connect()
+    }
+
+    fn reconnect(&mut self) -> JfResult<()> {
+        self.client.reconnect()
+    }
+
+    fn set_presence(&mut self, presence: &Presence) -> JfResult<()> {
+        // Discord requires a minimum length of 3 chars, invisible marks fill up shorter text
+        let pad = |text: &str| {
+            let mut text = text.to_string();
+            if text.len() < 3 {
+                text += "\u{200e}\u{200e}\u{200e}";
+            }
+            text
+        };
+
+        let details = pad(&presence.details);
+        let state = pad(&presence.state);
+        let image_text = pad(&presence.image_text);
+
+        let mut assets = Assets::new()
+            .large_image(&presence.image_url)
+            .large_text(&image_text);
+
+        if presence.paused {
+            assets = assets
+                .small_image("https://i.imgur.com/wlHSvYy.png")
+                .small_text("Paused");
+        }
+
+        let mut timestamps = Timestamps::new();
+
+        if let (Some(start), Some(end)) = (presence.start, presence.end) {
+            timestamps = timestamps.start(start).end(end);
+        }
+
+        let mut activity = Activity::new()
+            .timestamps(timestamps)
+            .assets(assets)
+            .details(&details)
+            .state(&state);
+
+        if !presence.buttons.is_empty() {
+            activity = activity.buttons(
+                presence
+                    .buttons
+                    .iter()
+                    .map(|b| ActButton::new(&b.name, &b.url))
+                    .collect(),
+            );
+        }
+
+        match presence.media_type {
+            MediaType::Book => (),
+            MediaType::Music | MediaType::AudioBook => {
+                activity = activity.activity_type(ActivityType::Listening)
+            }
+            _ => activity = activity.activity_type(ActivityType::Watching),
+        }
+
+        self.client.set_activity(activity)
+    }
+
+    fn clear_presence(&mut self) -> JfResult<()> {
+        self.client.clear_activity()
+    }
+}
@@ -0,0 +1,103 @@
+use url::Url;
+
+use crate::jellyfin::Session;
+use crate::{BlacklistAction, JfError, JfResult};
+
+/// Somewhere the client can find out what is being played.
+///
+/// Jellyfin and Emby servers (see [ServerBuilder](crate::ServerBuilder)) are sources,
+/// other players can be shown by implementing this and adding them with `ClientBuilder::source()`.
+/// Everything that isn't about getting the session, like the display formats,
+/// buttons and the connection to discord, is handled by the [Client](crate::Client).
+///
+/// Only `name()` and `get_session()` have to be implemented,
+/// the other methods default to a source without a blacklist, artwork or links.
+///
+/// # Example
+/// ```
+/// use jellyfin_rpc::{ClientBuilder, MediaType, NowPlayingItem, PlayState, Session, SessionSource};
+///
+/// struct Radio;
+///
+/// impl SessionSource for Radio {
+///     fn name(&self) -> String {
+///         "radio".to_string()
+///     }
+///
+///     fn get_session(&mut self) -> Result<Option<Session>, Box<dyn std::error::Error>> {
+///         let item = NowPlayingItem {
+///             name: "Night Drive".to_string(),
+///             media_type: MediaType::Music,
+///             id: "night-drive".to_string(),
+///             artists: Some(vec!["The Band".to_string()]),
+///             ..Default::default()
+///         };
+///
+///         Ok(Some(Session::new(item, PlayState::default())))
+///     }
+/// }
+///
+/// let mut builder = ClientBuilder::new();
+/// builder.source(Radio);
+///
+/// let client = builder.build().unwrap();
+/// ```
+pub trait SessionSource: Send {
+    /// Name of the source, only used in logs.
+    fn name(&self) -> String;
+
+    /// What is being played right now, `None` when nothing is.
+    ///
+    /// Sources are polled at the same time, each from its own thread.
+    fn get_session(&mut self) -> JfResult<Option<Session>>;
+
+    /// Which source is displayed when several are playing something,
+    /// see `ServerBuilder::priority()`.
+    ///
+    /// Defaults to `0`.
+    fn priority(&self) -> i32 {
+        0
+    }
+
+    /// Called before `session` is displayed, to load anything the other methods need.
+    ///
+    /// `links` is whether one of the configured buttons uses `SessionSource::link()`
+    /// or one of `SessionSource::button_placeholders()`.
+    fn prepare(&mut self, _session: &Session, _links: bool) {}
+
+    /// Forgets everything cached about items, see `Client::refresh_blacklist()`.
+    fn refresh(&mut self) -> JfResult<()> {
+        Ok(())
+    }
+
+    /// What should happen instead of showing `session` as usual, `None` shows it.
+    fn check_blacklist(&self, _session: &Session) -> Option<BlacklistAction> {
+        None
+    }
+
+    /// Url of the artwork for `session`, discord has to be able to load it.
+    fn image_url(&self, _session: &Session) -> JfResult<Url> {
+        Err(Box::new(JfError::NoImage))
+    }
+
+    /// Downloads the artwork for `session`, used when it's uploaded to an image host.
+    fn image(&self, session: &Session) -> JfResult<Vec<u8>> {
+        Ok(reqwest::blocking::get(self.image_url(session)?)?
+            .bytes()?
+            .to_vec())
+    }
+
+    /// Link to the item, used by buttons with `"jellyfin"` as their url.
+    /// Placeholders in it are filled in like in any other button url,
+    /// the button isn't shown when this is `None`.
+    fn link(&self, _session: &Session) -> Option<String> {
+        None
+    }
+
+    /// Values for placeholders that only this source knows, like `{server-id}`.
+    ///
+    /// Unlike other placeholders these are put into urls without escaping them.
+    fn button_placeholders(&self, _session: &Session) -> Vec<(&'static str, String)> {
+        Vec::new()
+    }
+}
@@ -1,10 +1,93 @@
-use crate::ClientBuilder;
+use crate::external::imgur;
+use crate::jellyfin::{NowPlayingItem, PlayState, RawSession, Session, VirtualFolder};
+use crate::rules::Rule;
+use crate::schedule::{CompiledSchedule, Window};
+use crate::{
+    Blacklist, BlacklistAction, BlacklistedLibraries, Button, ClientBuilder, ContentRule,
+    MediaType, Presence, PresenceSink, Schedule, ServerBuilder, ServerKind, SessionSource,
+    TimeWindow,
+};
+use chrono::{NaiveTime, Weekday};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+
+/// Plays whatever it's given, without talking to a server.
+struct MockSource {
+    priority: i32,
+    item: Option<NowPlayingItem>,
+    fail: bool,
+}
+
+impl SessionSource for MockSource {
+    fn name(&self) -> String {
+        "mock".to_string()
+    }
+
+    fn get_session(&mut self) -> crate::JfResult<Option<Session>> {
+        if self.fail {
+            return Err("unreachable".into());
+        }
+
+        Ok(self
+            .item
+            .clone()
+            .map(|item| Session::new(item, PlayState::default())))
+    }
+
+    fn priority(&self) -> i32 {
+        self.priority
+    }
+}
+
+/// Remembers everything it was asked to show, `None` for a cleared activity.
+#[derive(Clone, Default)]
+struct MockSink {
+    shown: Arc<Mutex<Vec<Option<Presence>>>>,
+}
+
+impl PresenceSink for MockSink {
+    fn name(&self) -> String {
+        "mock".to_string()
+    }
+
+    fn set_presence(&mut self, presence: &Presence) -> crate::JfResult<()> {
+        self.shown.lock().unwrap().push(Some(presence.clone()));
+        Ok(())
+    }
+
+    fn clear_presence(&mut self) -> crate::JfResult<()> {
+        self.shown.lock().unwrap().push(None);
+        Ok(())
+    }
+}
+
+/// Can't connect to anything.
+struct FailingSink;
+
+impl PresenceSink for FailingSink {
+    fn name(&self) -> String {
+        "failing".to_string()
+    }
+
+    fn connect(&mut self) -> crate::JfResult<()> {
+        Err("unreachable".into())
+    }
+
+    fn set_presence(&mut self, _: &Presence) -> crate::JfResult<()> {
+        Ok(())
+    }
+
+    fn clear_presence(&mut self) -> crate::JfResult<()> {
+        Ok(())
+    }
+}
 
 #[test]
 fn build_client_error() {
     let client = ClientBuilder::new().build();
 
-    if let Ok(_) = client {
+    if client.is_ok() {
         panic!("client was constructed even though required values are missing!");
     }
 }
@@ -19,7 +102,1060 @@ fn invalid_url() {
 
     let client = builder.build();
 
-    if let Ok(_) = client {
+    if client.is_ok() {
         panic!("client constructed without a valid url!")
     }
 }
+
+#[test]
+fn session_image_tags() {
+    let raw: RawSession = serde_json::from_str(
+        r#"{
+            "UserName": "test",
+            "NowPlayingItem": {
+                "Name": "Pilot",
+                "Type": "Episode",
+                "Id": "episode-id",
+                "SeriesId": "series-id",
+                "SeriesPrimaryImageTag": "series-tag",
+                "ImageTags": { "Primary": "episode-tag" }
+            },
+            "PlayState": { "IsPaused": false }
+        }"#,
+    )
+    .unwrap();
+    let session = raw.build();
+
+    assert_eq!(session.item_id, "series-id");
+    assert_eq!(session.image_tag.as_deref(), Some("series-tag"));
+
+    // New artwork gets a new tag, which has to be uploaded again
+    let mut builder = ClientBuilder::new();
+    builder.source(MockSource {
+        priority: 0,
+        item: None,
+        fail: false,
+    });
+    let mut client = builder.build().unwrap();
+
+    client.session = Some(session);
+    assert_eq!(imgur::cache_id(&client), "mock/series-id-series-tag");
+
+    let raw: RawSession = serde_json::from_str(
+        r#"{
+            "UserName": "test",
+            "NowPlayingItem": {
+                "Name": "Song",
+                "Type": "Audio",
+                "Id": "song-id",
+                "AlbumId": "album-id"
+            },
+            "PlayState": { "IsPaused": false }
+        }"#,
+    )
+    .unwrap();
+    let session = raw.build();
+
+    assert_eq!(session.item_id, "song-id");
+    assert_eq!(session.image_tag, None);
+
+    client.session = Some(session);
+    assert_eq!(imgur::cache_id(&client), "mock/song-id");
+}
+
+#[cfg(feature = "transform")]
+#[test]
+fn transform_square() {
+    use crate::{artwork, ImageTransform, SquareMode};
+    use image::{ImageFormat, RgbaImage};
+    use std::io::Cursor;
+
+    let mut poster = Cursor::new(Vec::new());
+    RgbaImage::new(40, 60)
+        .write_to(&mut poster, ImageFormat::Png)
+        .unwrap();
+
+    for square in [SquareMode::Crop, SquareMode::Pad] {
+        let options = ImageTransform {
+            square,
+            blur_backdrop: true,
+            progress_bar: true,
+            rating_badge: true,
+            size: 64,
+        };
+
+        let output = artwork::transform(poster.get_ref(), &options, Some(0.5), Some(7.5)).unwrap();
+        let output = image::load_from_memory(&output).unwrap();
+
+        assert_eq!((output.width(), output.height()), (64, 64));
+    }
+
+    let options = ImageTransform {
+        size: 100_000,
+        ..Default::default()
+    };
+
+    let output = artwork::transform(poster.get_ref(), &options, None, None).unwrap();
+    let output = image::load_from_memory(&output).unwrap();
+
+    assert_eq!((output.width(), output.height()), (1024, 1024));
+
+    // The progress bar and rating badge don't fit, but still mustn't break anything
+    for square in [SquareMode::Crop, SquareMode::Pad] {
+        let options = ImageTransform {
+            square,
+            blur_backdrop: true,
+            progress_bar: true,
+            rating_badge: true,
+            size: 1,
+        };
+
+        let output = artwork::transform(poster.get_ref(), &options, Some(0.5), Some(7.5)).unwrap();
+        let output = image::load_from_memory(&output).unwrap();
+
+        assert_eq!((output.width(), output.height()), (1, 1));
+    }
+}
+
+#[cfg(feature = "transform")]
+#[test]
+fn transform_cache_keys() {
+    use crate::{artwork, ImageTransform};
+
+    let options = ImageTransform {
+        progress_bar: true,
+        rating_badge: true,
+        ..Default::default()
+    };
+
+    let mut builder = ClientBuilder::new();
+    builder
+        .source(MockSource {
+            priority: 0,
+            item: None,
+            fail: false,
+        })
+        .image_transform(options.clone());
+    let mut client = builder.build().unwrap();
+
+    let item = NowPlayingItem {
+        name: "Up".to_string(),
+        media_type: MediaType::Movie,
+        id: "up".to_string(),
+        primary_image_tag: Some("tag".to_string()),
+        community_rating: Some(8.3),
+        run_time_ticks: Some(10_000_000 * 60 * 96),
+        ..Default::default()
+    };
+    let play_state = PlayState {
+        position_ticks: Some(10_000_000 * 60 * 24),
+        ..Default::default()
+    };
+    client.session = Some(Session::new(item, play_state));
+
+    let id = imgur::cache_id(&client);
+    assert!(id.starts_with("mock/up-tag-"));
+
+    // Other progress steps of the same image are replaced, anything else is kept
+    let stale = artwork::stale_progress(&id, &options).unwrap();
+    let key = |progress, rating| format!("mock/up-tag-{}", options.cache_key(progress, rating));
+
+    assert!(id.starts_with(&stale));
+    assert!(key(Some(0.75), Some(8.3)).starts_with(&stale));
+    assert!(!key(Some(0.75), Some(3.0)).starts_with(&stale));
+    assert!(!format!(
+        "mock/up-new-tag-{}",
+        options.cache_key(Some(0.25), Some(8.3))
+    )
+    .starts_with(&stale));
+    assert!(
+        !format!("other/up-tag-{}", options.cache_key(Some(0.25), Some(8.3))).starts_with(&stale)
+    );
+
+    let options = ImageTransform::default();
+    assert_eq!(artwork::stale_progress(&id, &options), None);
+}
+
+#[test]
+fn templated_buttons() {
+    let mut server = ServerBuilder::new();
+    server
+        .api_key("a1b2c3d4")
+        .username("test")
+        .url("http://192.168.1.2:8096/")
+        .public_url("https://jellyfin.example.com/");
+
+    let mut server = server.build().unwrap();
+    server.server_id = Some("server-id".to_string());
+
+    let mut builder = ClientBuilder::new();
+    builder.source(server);
+
+    let mut client = builder.build().unwrap();
+
+    let raw: RawSession = serde_json::from_str(
+        r#"{
+            "UserName": "test",
+            "NowPlayingItem": {
+                "Name": "Spirited Away",
+                "Type": "Movie",
+                "Id": "movie-id",
+                "ProviderIds": { "Imdb": "tt0245429" }
+            },
+            "PlayState": { "IsPaused": false }
+        }"#,
+    )
+    .unwrap();
+    client.session = Some(raw.build());
+
+    let button = client
+        .parse_button(&Button::new(
+            "{title} on IMDb".to_string(),
+            "https://www.imdb.com/title/{imdb-id}".to_string(),
+        ))
+        .unwrap();
+    assert_eq!(button.name, "Spirited Away on IMDb");
+    assert_eq!(button.url, "https://www.imdb.com/title/tt0245429");
+
+    let button = client
+        .parse_button(&Button::new(
+            "Search".to_string(),
+            "https://letterboxd.com/search/{title}/".to_string(),
+        ))
+        .unwrap();
+    assert_eq!(button.url, "https://letterboxd.com/search/Spirited%20Away/");
+
+    let button = client
+        .parse_button(&Button::new(
+            "Watch".to_string(),
+            "{public-url}/web/#/details?id={item-id}".to_string(),
+        ))
+        .unwrap();
+    assert_eq!(
+        button.url,
+        "https://jellyfin.example.com/web/#/details?id=movie-id"
+    );
+
+    let button = client
+        .parse_button(&Button::new(
+            "Open in Jellyfin".to_string(),
+            "jellyfin".to_string(),
+        ))
+        .unwrap();
+    assert_eq!(
+        button.url,
+        "https://jellyfin.example.com/web/#/details?id=movie-id&serverId=server-id"
+    );
+
+    assert!(client
+        .parse_button(&Button::new(
+            "TMDB".to_string(),
+            "https://www.themoviedb.org/movie/{tmdb-id}".to_string(),
+        ))
+        .is_none());
+}
+
+#[test]
+fn jellyfin_button_access() {
+    let mut server = ServerBuilder::new();
+    server
+        .api_key("a1b2c3d4")
+        .username("test")
+        .url("http://127.0.0.1:9/")
+        .jellyfin_button_user("guest");
+
+    let mut server = server.build().unwrap();
+
+    let item = |id: &str| NowPlayingItem {
+        name: "Up".to_string(),
+        media_type: MediaType::Movie,
+        id: id.to_string(),
+        ..Default::default()
+    };
+    let public = Session::new(item("public"), PlayState::default());
+    let private = Session::new(item("private"), PlayState::default());
+    let unknown = Session::new(item("unknown"), PlayState::default());
+
+    let now = SystemTime::now();
+    let expired = now - std::time::Duration::from_secs(7200);
+    server.jellyfin_button.accessible = HashMap::from([
+        ("public".to_string(), (true, now)),
+        ("private".to_string(), (false, now)),
+        ("old".to_string(), (true, expired)),
+    ]);
+
+    assert!(server.link(&public).is_some());
+    assert!(server.link(&private).is_none());
+    assert!(server.link(&unknown).is_none());
+
+    // Cached items aren't checked again, expired ones are dropped
+    server.check_item_access("public").unwrap();
+    assert_eq!(server.jellyfin_button.accessible.len(), 2);
+
+    // Nothing listens on the discard port, the cache is cleared before that fails
+    assert!(server.refresh_blacklist().is_err());
+    assert!(server.jellyfin_button.accessible.is_empty());
+
+    // The server id isn't asked for again right after it failed
+    server.jellyfin_button.accessible = HashMap::from([("public".to_string(), (true, now))]);
+    server.prepare(&public, true);
+    let (retry_at, wait) = server.server_id_retry.unwrap();
+    assert_eq!(wait.as_secs(), 30);
+
+    server.prepare(&public, true);
+    assert_eq!(server.server_id_retry, Some((retry_at, wait)));
+}
+
+#[test]
+fn dynamic_button_providers() {
+    let mut builder = ClientBuilder::new();
+    builder
+        .api_key("a1b2c3d4")
+        .username("test")
+        .url("http://192.168.1.2:8096/")
+        .buttons(vec![Button::default(), Button::default()])
+        .dynamic_button_providers(vec!["IMDb".to_string(), "Trakt".to_string()])
+        .dynamic_button_labels(HashMap::from([
+            ("imdb".to_string(), "View on IMDb".to_string()),
+            ("IMDB".to_string(), "Open IMDb".to_string()),
+            (
+                "TheTVDB".to_string(),
+                "Look this episode up on TheTVDB.com".to_string(),
+            ),
+        ]));
+
+    let mut client = builder.build().unwrap();
+
+    let raw: RawSession = serde_json::from_str(
+        r#"{
+            "UserName": "test",
+            "NowPlayingItem": {
+                "Name": "Pilot",
+                "Type": "Episode",
+                "Id": "episode-id",
+                "ExternalUrls": [
+                    { "Name": "TheTVDB", "Url": "https://thetvdb.com/1" },
+                    { "Name": "TheMovieDb", "Url": "https://www.themoviedb.org/2" },
+                    { "Name": "IMDb", "Url": "https://www.imdb.com/title/tt3" }
+                ]
+            },
+            "PlayState": { "IsPaused": false }
+        }"#,
+    )
+    .unwrap();
+    client.session = Some(raw.build());
+
+    // Trakt is missing, so the first remaining provider is used instead.
+    // "imdb" sorts after "IMDB" so its label wins, long labels are cut off at 32 characters
+    assert_eq!(
+        client.get_buttons().unwrap(),
+        vec![
+            Button::new(
+                "View on IMDb".to_string(),
+                "https://www.imdb.com/title/tt3".to_string()
+            ),
+            Button::new(
+                "Look this episode up on TheTVDB.".to_string(),
+                "https://thetvdb.com/1".to_string()
+            ),
+        ]
+    );
+}
+
+#[test]
+fn library_whitelist() {
+    let mut builder = ServerBuilder::new();
+    builder
+        .api_key("a1b2c3d4")
+        .username("test")
+        .url("http://192.168.1.2:8096/")
+        .blacklist_libraries(vec!["Anime".to_string()])
+        .whitelist_libraries(vec!["Movies".to_string(), "Anime".to_string()]);
+
+    let mut server = builder.build().unwrap();
+
+    let folders: Vec<VirtualFolder> = serde_json::from_str(
+        r#"[
+            { "Name": "Movies", "Locations": ["/media/movies"] },
+            { "Name": "Anime", "Locations": ["/media/anime"] },
+            { "Name": "Shows", "Locations": ["/media/shows"] }
+        ]"#,
+    )
+    .unwrap();
+    server.blacklist.libraries = BlacklistedLibraries::Initialized(folders, SystemTime::now());
+
+    let item = |path: &str| -> NowPlayingItem {
+        serde_json::from_value(serde_json::json!({
+            "Name": "Item",
+            "Type": "Movie",
+            "Id": "item-id",
+            "Path": path,
+        }))
+        .unwrap()
+    };
+
+    assert!(!server.blacklist.check_item(&item("/media/movies/Up.mkv")));
+    assert!(server.blacklist.check_item(&item("/media/anime/Akira.mkv")));
+    assert!(server.blacklist.check_item(&item("/media/shows/Lost.mkv")));
+}
+
+#[test]
+fn content_rules() {
+    let rules: Vec<ContentRule> = serde_json::from_str(
+        r#"[
+            { "genres": ["adult"] },
+            { "min_rating": "R", "any": [{ "tags": ["Gore"] }, { "title": "(?i)^saw" }] }
+        ]"#,
+    )
+    .unwrap();
+    let rules: Vec<Rule> = rules.iter().map(|r| Rule::compile(r).unwrap()).collect();
+
+    let item = |value: serde_json::Value| -> NowPlayingItem {
+        let mut item = serde_json::json!({ "Name": "Item", "Type": "Movie", "Id": "item-id" });
+        item.as_object_mut()
+            .unwrap()
+            .extend(value.as_object().unwrap().clone());
+        serde_json::from_value(item).unwrap()
+    };
+    let hidden = |item: &NowPlayingItem| {
+        let tags = item.tags.clone().unwrap_or_default();
+        rules.iter().any(|r| r.matches(item, &tags))
+    };
+
+    assert!(hidden(&item(serde_json::json!({ "Genres": ["Adult"] }))));
+    assert!(hidden(&item(
+        serde_json::json!({ "Name": "Saw II", "OfficialRating": "R" })
+    )));
+    assert!(hidden(&item(
+        serde_json::json!({ "OfficialRating": "FSK-18", "Tags": ["gore"] })
+    )));
+    assert!(!hidden(&item(
+        serde_json::json!({ "Name": "Saw II", "OfficialRating": "PG-13" })
+    )));
+    assert!(!hidden(&item(
+        serde_json::json!({ "OfficialRating": "NC-17" })
+    )));
+    assert!(Rule::compile(&ContentRule {
+        title: Some("(".to_string()),
+        ..Default::default()
+    })
+    .is_err());
+}
+
+#[test]
+fn tag_rules() {
+    let mut server = ServerBuilder::new();
+    server
+        .api_key("a1b2c3d4")
+        .username("test")
+        .url("http://192.168.1.2:8096/")
+        .blacklist_rules(vec![ContentRule {
+            any: Some(vec![ContentRule {
+                tags: Some(vec!["Gore".to_string()]),
+                ..Default::default()
+            }]),
+            ..Default::default()
+        }]);
+
+    let mut server = server.build().unwrap();
+    assert!(server.blacklist.needs_tags());
+
+    // Sessions don't include tags, only the ones fetched from /Items are there
+    let item: NowPlayingItem = serde_json::from_value(serde_json::json!({
+        "Name": "Pilot",
+        "Type": "Episode",
+        "Id": "episode-id",
+        "SeriesId": "series-id",
+    }))
+    .unwrap();
+    assert_eq!(server.blacklist.check(&item), None);
+
+    server.blacklist.item_tags = HashMap::from([
+        ("episode-id".to_string(), Vec::new()),
+        ("series-id".to_string(), vec!["gore".to_string()]),
+    ]);
+    assert_eq!(server.blacklist.check(&item), Some(BlacklistAction::Hide));
+}
+
+#[test]
+fn private_tags() {
+    let mut server = ServerBuilder::new();
+    server
+        .api_key("a1b2c3d4")
+        .username("test")
+        .url("http://192.168.1.2:8096/")
+        .private_tags(vec!["Private".to_string()]);
+
+    let mut server = server.build().unwrap();
+
+    let episode: NowPlayingItem = serde_json::from_value(serde_json::json!({
+        "Name": "Pilot",
+        "Type": "Episode",
+        "Id": "episode-id",
+        "SeasonId": "season-id",
+        "SeriesId": "series-id",
+    }))
+    .unwrap();
+    let song: NowPlayingItem = serde_json::from_value(serde_json::json!({
+        "Name": "Song",
+        "Type": "Audio",
+        "Id": "song-id",
+        "AlbumId": "album-id",
+    }))
+    .unwrap();
+
+    assert_eq!(
+        Blacklist::tagged_ids(&episode),
+        vec!["episode-id", "season-id", "series-id"]
+    );
+    assert_eq!(Blacklist::tagged_ids(&song), vec!["song-id", "album-id"]);
+
+    let tags = |tagged: &[(&str, &str)]| -> HashMap<String, Vec<String>> {
+        [
+            "episode-id",
+            "season-id",
+            "series-id",
+            "song-id",
+            "album-id",
+        ]
+        .into_iter()
+        .map(|id| {
+            let tags = tagged
+                .iter()
+                .filter(|(tagged_id, _)| *tagged_id == id)
+                .map(|(_, tag)| tag.to_string())
+                .collect();
+            (id.to_string(), tags)
+        })
+        .collect()
+    };
+
+    // Tags are inherited from every parent and matched regardless of case
+    for (id, tag) in [
+        ("episode-id", "private"),
+        ("season-id", "PRIVATE"),
+        ("series-id", "Private"),
+    ] {
+        server.blacklist.item_tags = tags(&[(id, tag)]);
+        assert_eq!(
+            server.blacklist.check(&episode),
+            Some(BlacklistAction::Hide),
+            "{} tagged {}",
+            id,
+            tag
+        );
+    }
+
+    server.blacklist.item_tags = tags(&[("album-id", "private")]);
+    assert_eq!(server.blacklist.check(&song), Some(BlacklistAction::Hide));
+
+    server.blacklist.item_tags = tags(&[("series-id", "Privately owned")]);
+    assert_eq!(server.blacklist.check(&episode), None);
+
+    // Without the tags the item is shown, unless the blacklist fails closed
+    server.blacklist.item_tags = HashMap::new();
+    assert_eq!(server.blacklist.check(&episode), None);
+
+    server.blacklist.fail_closed = true;
+    assert_eq!(
+        server.blacklist.check(&episode),
+        Some(BlacklistAction::Hide)
+    );
+
+    server.blacklist.item_tags = tags(&[]);
+    assert_eq!(server.blacklist.check(&episode), None);
+}
+
+#[test]
+fn blacklist_actions() {
+    let actions: Vec<BlacklistAction> = serde_json::from_str(
+        r#"["hide", "media_type", { "redact": { "text": "Busy", "image": null } }]"#,
+    )
+    .unwrap();
+
+    assert_eq!(actions[0], BlacklistAction::Hide);
+    assert_eq!(actions[1].details(MediaType::Movie), "Watching a movie");
+    assert_eq!(actions[2].details(MediaType::Music), "Busy");
+    assert_eq!(
+        BlacklistAction::Redact {
+            text: None,
+            image: None
+        }
+        .details(MediaType::AudioBook),
+        "Listening to something"
+    );
+
+    let most_restrictive = actions
+        .iter()
+        .max_by_key(|action| action.restrictiveness())
+        .unwrap();
+    assert_eq!(most_restrictive, &BlacklistAction::Hide);
+}
+
+#[test]
+fn schedule_windows() {
+    let time = |t: &str| NaiveTime::parse_from_str(t, "%H:%M").unwrap();
+    let window = |days: Option<Vec<Weekday>>, start: &str, end: &str| {
+        Window::compile(&TimeWindow {
+            days,
+            start: start.to_string(),
+            end: end.to_string(),
+        })
+        .unwrap()
+    };
+
+    let work = window(Some(vec![Weekday::Mon, Weekday::Fri]), "08:00", "16:00");
+    assert!(work.contains(Weekday::Mon, time("08:00")));
+    assert!(!work.contains(Weekday::Mon, time("16:00")));
+    assert!(!work.contains(Weekday::Tue, time("12:00")));
+
+    // Friday night into saturday morning
+    let night = window(Some(vec![Weekday::Fri]), "23:00", "02:00");
+    assert!(night.contains(Weekday::Fri, time("23:30")));
+    assert!(night.contains(Weekday::Sat, time("01:59")));
+    assert!(!night.contains(Weekday::Fri, time("01:00")));
+    assert!(!night.contains(Weekday::Sat, time("23:30")));
+
+    let all_day = window(None, "00:00", "00:00");
+    assert!(all_day.contains(Weekday::Wed, time("13:37")));
+
+    assert!(Window::compile(&TimeWindow {
+        days: None,
+        start: "25:00".to_string(),
+        end: "26:00".to_string(),
+    })
+    .is_err());
+    assert!(CompiledSchedule::compile(Schedule {
+        timezone: Some("Not/A_Zone".to_string()),
+        ..Default::default()
+    })
+    .is_err());
+}
+
+#[test]
+fn library_matching() {
+    let mut builder = ServerBuilder::new();
+    builder
+        .api_key("a1b2c3d4")
+        .username("test")
+        .url("http://192.168.1.2:8096/")
+        .blacklist_libraries(vec!["Anime".to_string(), "Windows".to_string()]);
+
+    let mut server = builder.build().unwrap();
+
+    let folders: Vec<VirtualFolder> = serde_json::from_str(
+        r#"[
+            { "Name": "Anime", "Locations": ["/media/Anime/"], "ItemId": "anime-id" },
+            { "Name": "Anime Movies", "Locations": ["/media/Anime Movies"], "ItemId": "movies-id" },
+            { "Name": "Windows", "Locations": ["\\\\NAS\\Share\\Shows"] }
+        ]"#,
+    )
+    .unwrap();
+    server.blacklist.libraries = BlacklistedLibraries::Initialized(folders, SystemTime::now());
+
+    let item = |id: &str, path: Option<&str>| -> NowPlayingItem {
+        serde_json::from_value(serde_json::json!({
+            "Name": "Item",
+            "Type": "Movie",
+            "Id": id,
+            "Path": path,
+        }))
+        .unwrap()
+    };
+
+    // Path fallback respects component boundaries
+    assert!(server
+        .blacklist
+        .check_item(&item("a", Some("/media/Anime/Akira.mkv"))));
+    assert!(!server
+        .blacklist
+        .check_item(&item("b", Some("/media/Anime Movies/Akira.mkv"))));
+    assert!(server
+        .blacklist
+        .check_item(&item("c", Some("//nas/share/shows/Lost/S01E01.mkv"))));
+
+    // Ancestors are used before the path, even when there is no path
+    server
+        .blacklist
+        .item_ancestors
+        .insert("d".to_string(), vec!["anime-id".to_string()]);
+    server
+        .blacklist
+        .item_ancestors
+        .insert("e".to_string(), vec!["movies-id".to_string()]);
+    assert!(server.blacklist.check_item(&item("d", None)));
+    assert!(!server
+        .blacklist
+        .check_item(&item("e", Some("/media/Anime/Akira.mkv"))));
+}
+
+#[test]
+fn server_priority() {
+    let mut friend = ServerBuilder::new();
+    friend
+        .name("friend")
+        .url("https://friend.example.com")
+        .api_key("e5f6g7h8")
+        .username("me");
+
+    let mut work = friend.clone();
+    work.name("work")
+        .url("https://work.example.com")
+        .priority(1);
+
+    let mut builder = ClientBuilder::new();
+    builder
+        .api_key("a1b2c3d4")
+        .username("test")
+        .url("https://home.example.com")
+        .server(friend)
+        .server(work);
+
+    let client = builder.build().unwrap();
+    assert_eq!(client.sources.len(), 3);
+    assert_eq!(client.sources[0].name(), "home.example.com");
+
+    let session = |paused: bool| {
+        let raw: RawSession = serde_json::from_value(serde_json::json!({
+            "UserName": "me",
+            "NowPlayingItem": { "Name": "Up", "Type": "Movie", "Id": "up" },
+            "PlayState": { "IsPaused": paused }
+        }))
+        .unwrap();
+        raw.build()
+    };
+
+    let picked = |sessions: Vec<(usize, bool)>| {
+        client
+            .pick_session(
+                sessions
+                    .into_iter()
+                    .map(|(index, paused)| (index, session(paused)))
+                    .collect(),
+            )
+            .map(|(index, _)| index)
+    };
+
+    assert_eq!(picked(vec![]), None);
+    assert_eq!(picked(vec![(0, false), (1, false)]), Some(0));
+    assert_eq!(picked(vec![(0, false), (1, false), (2, false)]), Some(2));
+    assert_eq!(picked(vec![(0, false), (2, true)]), Some(0));
+    assert_eq!(picked(vec![(0, true), (1, true)]), Some(0));
+}
+
+#[test]
+fn emby_sessions() {
+    let sessions = || {
+        ServerKind::Emby
+            .parse_sessions(include_str!("../fixtures/emby-sessions.json"))
+            .unwrap()
+    };
+    assert_eq!(sessions().len(), 3);
+
+    let mut server = ServerBuilder::new();
+    server
+        .kind(ServerKind::Emby)
+        .url("https://emby.example.com/")
+        .api_key("a1b2c3d4")
+        .username("me");
+    let server = server.build().unwrap();
+
+    assert_eq!(
+        server.endpoint("Sessions").unwrap().as_str(),
+        "https://emby.example.com/emby/Sessions"
+    );
+
+    let session = server.find_session(sessions()).unwrap();
+    assert_eq!(session.now_playing_item.media_type, MediaType::Episode);
+    assert_eq!(session.item_id, "48101");
+
+    assert_eq!(
+        server.image_url(&session).unwrap().as_str(),
+        "https://emby.example.com/emby/Items/48101/Images/Primary?tag=e2f1a9c3b7d54c08a6f9e1d2c3b4a5f6"
+    );
+    assert_eq!(
+        server
+            .user_item_endpoint("user-id", &session.item_id)
+            .unwrap()
+            .as_str(),
+        "https://emby.example.com/emby/Users/user-id/Items/48101"
+    );
+
+    let mut tv = ServerBuilder::new();
+    tv.kind(ServerKind::Emby)
+        .url("https://emby.example.com/")
+        .api_key("a1b2c3d4")
+        .username("tv");
+    let tv = tv.build().unwrap();
+
+    // Emby calls live tv programs "Program"
+    let session = tv.find_session(sessions()).unwrap();
+    assert_eq!(session.now_playing_item.media_type, MediaType::LiveTv);
+    assert!(session.play_state.is_paused);
+
+    let jellyfin = ServerKind::Jellyfin
+        .parse_sessions(include_str!("../fixtures/emby-sessions.json"))
+        .unwrap();
+    assert_eq!(
+        jellyfin[2].now_playing_item.as_ref().unwrap().media_type,
+        MediaType::None
+    );
+
+    let headers = ServerKind::Emby.headers("a1b2c3d4").unwrap();
+    assert_eq!(headers["X-Emby-Token"], "a1b2c3d4");
+    assert!(!headers.contains_key("Authorization"));
+
+    let headers = ServerKind::Jellyfin.headers("a1b2c3d4").unwrap();
+    assert_eq!(headers["Authorization"], "MediaBrowser Token=\"a1b2c3d4\"");
+    assert_eq!(headers["X-Emby-Token"], "a1b2c3d4");
+}
+
+#[test]
+fn session_sources() {
+    let song = NowPlayingItem {
+        name: "Night Drive".to_string(),
+        media_type: MediaType::Music,
+        id: "night-drive".to_string(),
+        artists: Some(vec!["The Band".to_string(), "Someone".to_string()]),
+        ..Default::default()
+    };
+    let movie = NowPlayingItem {
+        name: "Up".to_string(),
+        media_type: MediaType::Movie,
+        id: "up".to_string(),
+        ..Default::default()
+    };
+
+    // No jellyfin server is needed when there are other sources
+    let mut builder = ClientBuilder::new();
+    builder
+        .source(MockSource {
+            priority: 0,
+            item: None,
+            fail: false,
+        })
+        .source(MockSource {
+            priority: 0,
+            item: Some(song),
+            fail: false,
+        })
+        .source(MockSource {
+            priority: 0,
+            item: None,
+            fail: true,
+        });
+
+    let mut client = builder.build().unwrap();
+    assert_eq!(client.sources.len(), 3);
+
+    client.get_session().unwrap();
+    assert_eq!(client.source, 1);
+    assert_eq!(client.get_details(), "Night Drive");
+    assert_eq!(client.get_state(), "By The Band and Someone");
+    assert!(client.get_image().is_err());
+
+    builder = ClientBuilder::new();
+    builder
+        .source(MockSource {
+            priority: 0,
+            item: Some(movie.clone()),
+            fail: false,
+        })
+        .source(MockSource {
+            priority: 1,
+            item: Some(movie),
+            fail: false,
+        });
+
+    let mut client = builder.build().unwrap();
+    client.get_session().unwrap();
+    assert_eq!(client.source, 1);
+
+    builder = ClientBuilder::new();
+    builder.source(MockSource {
+        priority: 0,
+        item: None,
+        fail: true,
+    });
+
+    let mut client = builder.build().unwrap();
+    assert!(client.get_session().is_err());
+}
+
+#[test]
+fn presence_sinks() {
+    let song = NowPlayingItem {
+        name: "Night Drive".to_string(),
+        media_type: MediaType::Music,
+        id: "night-drive".to_string(),
+        artists: Some(vec!["The Band".to_string()]),
+        ..Default::default()
+    };
+    let sink = MockSink::default();
+
+    let mut builder = ClientBuilder::new();
+    builder
+        .source(MockSource {
+            priority: 0,
+            item: Some(song),
+            fail: false,
+        })
+        .discord(false)
+        .show_images(false)
+        .sink(sink.clone());
+
+    let mut client = builder.build().unwrap();
+    assert!(client.discord.is_none());
+
+    client.connect().unwrap();
+    assert_eq!(client.set_activity().unwrap(), "Night Drive | By The Band");
+    client.clear_activity().unwrap();
+
+    let shown = sink.shown.lock().unwrap();
+    assert_eq!(shown.len(), 2);
+    assert!(shown[1].is_none());
+
+    let presence = shown[0].as_ref().unwrap();
+    assert_eq!(presence.details, "Night Drive");
+    assert_eq!(presence.state, "By The Band");
+    assert_eq!(presence.media_type, MediaType::Music);
+    assert_eq!(presence.image_url, "https://i.imgur.com/oX6vcds.png");
+    // The mock source doesn't know the position, so there are no timestamps
+    assert!(!presence.paused);
+    assert_eq!(presence.start, None);
+    assert_eq!(presence.progress, None);
+}
+
+#[test]
+fn update_failure() {
+    let source = || MockSource {
+        priority: 0,
+        item: None,
+        fail: false,
+    };
+    let sink = MockSink::default();
+
+    let mut builder = ClientBuilder::new();
+    builder.source(source()).client_id("1").sink(sink.clone());
+    let mut client = builder.build().unwrap();
+
+    let mut builder = ClientBuilder::new();
+    builder.source(source()).client_id("2").sink(FailingSink);
+    assert!(client.update(builder).is_err());
+
+    // Nothing was replaced or closed
+    assert_eq!(client.discord.as_ref().unwrap().client.client_id, "1");
+    client.clear_activity().ok();
+    assert_eq!(sink.shown.lock().unwrap().len(), 1);
+}
+
+#[test]
+fn redacted_activity() {
+    /// Plays a movie halfway through that is always redacted.
+    struct Redacted;
+
+    impl SessionSource for Redacted {
+        fn name(&self) -> String {
+            "redacted".to_string()
+        }
+
+        fn get_session(&mut self) -> crate::JfResult<Option<Session>> {
+            let item = NowPlayingItem {
+                name: "Secret".to_string(),
+                media_type: MediaType::Movie,
+                id: "secret".to_string(),
+                run_time_ticks: Some(10_000_000 * 60 * 90),
+                ..Default::default()
+            };
+            let play_state = PlayState {
+                position_ticks: Some(10_000_000 * 60 * 45),
+                ..Default::default()
+            };
+
+            Ok(Some(Session::new(item, play_state)))
+        }
+
+        fn check_blacklist(&self, _session: &Session) -> Option<BlacklistAction> {
+            Some(BlacklistAction::Redact {
+                text: None,
+                image: None,
+            })
+        }
+    }
+
+    let sink = MockSink::default();
+
+    let mut builder = ClientBuilder::new();
+    builder.source(Redacted).discord(false).sink(sink.clone());
+
+    let mut client = builder.build().unwrap();
+    client.set_activity().unwrap();
+
+    let shown = sink.shown.lock().unwrap();
+    let presence = shown[0].as_ref().unwrap();
+
+    assert_eq!(presence.details, "Watching something");
+    assert_eq!((presence.start, presence.end), (None, None));
+    assert_eq!(presence.progress, None);
+}
+
+#[test]
+fn blacklist_cache() {
+    assert_eq!(ClientBuilder::default().server.blacklist_cache_ttl, 3600);
+    assert_eq!(ServerBuilder::default().blacklist_cache_ttl, 3600);
+
+    let mut server = ServerBuilder::new();
+    server
+        .api_key("a1b2c3d4")
+        .username("test")
+        // Nothing listens on the discard port, so loading the library list fails right away
+        .url("http://127.0.0.1:9/")
+        .blacklist_libraries(vec!["Anime".to_string()]);
+
+    let mut server = server.build().unwrap();
+
+    let session = Session::new(
+        NowPlayingItem {
+            name: "Akira".to_string(),
+            media_type: MediaType::Movie,
+            id: "akira".to_string(),
+            ..Default::default()
+        },
+        PlayState::default(),
+    );
+    let loaded = |time: SystemTime| BlacklistedLibraries::Initialized(Vec::new(), time);
+    let is_loaded = |server: &crate::server::Server| {
+        matches!(
+            server.blacklist.libraries,
+            BlacklistedLibraries::Initialized(..)
+        )
+    };
+
+    // The list is kept while the cache is valid
+    server.blacklist.libraries = loaded(SystemTime::now());
+    server.prepare(&session, false);
+    assert!(is_loaded(&server));
+
+    // Once it expires it's loaded again, which fails here
+    server.blacklist.libraries = loaded(SystemTime::now() - std::time::Duration::from_secs(7200));
+    server.prepare(&session, false);
+    assert!(!is_loaded(&server));
+
+    // Content is shown while the list isn't loaded, unless the blacklist fails closed
+    assert_eq!(server.blacklist.check(&session.now_playing_item), None);
+    server.blacklist.fail_closed = true;
+    assert_eq!(
+        server.blacklist.check(&session.now_playing_item),
+        Some(BlacklistAction::Hide)
+    );
+
+    server.blacklist.libraries = loaded(SystemTime::now());
+    assert_eq!(server.blacklist.check(&session.now_playing_item), None);
+
+    // Refreshing forgets everything that was cached about items
+    server.blacklist.item_tags = HashMap::from([("akira".to_string(), Vec::new())]);
+    server.blacklist.item_ancestors = HashMap::from([("akira".to_string(), Vec::new())]);
+    assert!(server.refresh_blacklist().is_err());
+    assert!(server.blacklist.item_tags.is_empty());
+    assert!(server.blacklist.item_ancestors.is_empty());
+}